@@ -90,3 +90,19 @@ impl From<near_primitives::views::FinalExecutionOutcomeViewEnum> for RpcTransact
         }
     }
 }
+
+impl From<RpcTransactionError> for crate::errors::RpcError {
+    fn from(error: RpcTransactionError) -> Self {
+        let error_data = Some(Value::String(error.to_string()));
+        let error_data_value = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcTransactionError: {:?}", err),
+                )
+            }
+        };
+        Self::new_internal_or_handler_error(error_data, error_data_value)
+    }
+}
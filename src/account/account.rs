@@ -1,225 +1,1004 @@
-use core::panic;
-use std::io::Error;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
 
+use borsh::BorshDeserialize;
 use hashbrown::HashMap;
 use near_account_id::AccountId;
-use near_crypto::PublicKey;
-use near_jsonrpc_primitives::{errors::RpcError, message::Response};
+use near_crypto::{InMemorySigner, KeyType, PublicKey, SecretKey, Signer};
+use near_jsonrpc_primitives::{
+    errors::{RpcError, RpcErrorKind},
+    types::query::RpcQueryError,
+};
 use near_primitives::{
-    transaction::Action,
+    transaction::{
+        Action, AddKeyAction, CreateAccountAction, DeleteAccountAction, DeleteKeyAction,
+        DeployContractAction, FunctionCallAction, SignedTransaction, StakeAction, Transaction,
+        TransferAction,
+    },
     types::{Balance, BlockReference, Finality},
-    views::{AccessKeyInfoView, AccessKeyView, QueryResponse},
+    views::{
+        AccessKeyInfoView, AccessKeyPermissionView, AccessKeyView, AccountView, CallResult,
+        QueryRequest,
+    },
 };
-use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
 
 use crate::{
-    provider::types::{FinalExecutionOutcome, QueryResponseKind, RpcQueryRequest},
+    key_stores::{AsyncKeyStore, KeyPair},
+    outcome::{check_outcome, ReceiptTree},
+    provider::types::{CryptoHash, FinalExecutionOutcome, QueryResponseKind, RpcQueryRequest},
+    transaction::{full_access_key, function_call_access_key},
     Connection,
 };
 
-use super::{AccountAuthorizedApp, AccountBalance, FunctionCallOptions, ReceiptLogWithFailure};
+use super::{
+    AccessKeyAudit, AccessKeyAuditEntry, AccessKeyRisk, AccountAuthorizedApp, AccountBalance,
+    AccountError, ContractInitOptions, FunctionCallOptions, KeyRotationReport,
+    DEFAULT_FUNCTION_CALL_GAS,
+};
+
+fn unexpected_query_response() -> AccountError {
+    RpcError::parse_error("Unexpected query response".to_string()).into()
+}
+
+fn query_error(error: &RpcError) -> Option<RpcQueryError> {
+    match &error.error_struct {
+        Some(RpcErrorKind::HandlerError(cause)) => serde_json::from_value(cause.clone()).ok(),
+        _ => None,
+    }
+}
+
+/// The old and new key of the account's rotation: the persisted ones of an interrupted rotation,
+/// or the default key and a fresh key, which are persisted before the new key goes on chain so
+/// that it can not be lost.
+async fn load_rotation_keys<K: AsyncKeyStore + ?Sized>(
+    key_store: &mut K,
+    account_id: &AccountId,
+    network_id: &str,
+) -> Result<[KeyPair; 2], AccountError> {
+    let rotation_keys = key_store.get_rotation_keys_async(account_id, network_id).await?;
+
+    if let Ok(rotation_keys) = <[KeyPair; 2]>::try_from(rotation_keys) {
+        return Ok(rotation_keys);
+    }
+
+    let old_key = key_store
+        .get_key_async(account_id, network_id)
+        .await?
+        .ok_or_else(|| AccountError::MissingKey {
+            account_id: account_id.clone(),
+            network_id: network_id.to_string(),
+        })?;
+    let new_key = SecretKey::from_random(KeyType::ED25519);
+
+    key_store
+        .set_rotation_keys_async(account_id, network_id, vec![old_key.clone(), new_key.clone()])
+        .await?;
+    Ok([old_key, new_key])
+}
+
+/// Makes the rotation's new key the account's default key, dropping the old one and keeping the
+/// account's other keys.
+async fn promote_rotated_key<K: AsyncKeyStore + ?Sized>(
+    key_store: &mut K,
+    account_id: &AccountId,
+    network_id: &str,
+    old_public_key: &PublicKey,
+    new_key: KeyPair,
+) -> Result<(), AccountError> {
+    let new_public_key = new_key.public_key();
+    let mut key_pairs = key_store.get_keys_async(account_id, network_id).await?;
+
+    key_pairs.retain(|key_pair| {
+        let public_key = key_pair.public_key();
+        &public_key != old_public_key && public_key != new_public_key
+    });
+    key_pairs.insert(0, new_key);
+    key_store.set_keys_async(account_id, network_id, key_pairs).await?;
+    Ok(())
+}
+
+/// The key an account signs with and the cached access keys of the account.
+struct SigningState {
+    signer: Option<Arc<dyn Signer>>,
+    access_key_cache: HashMap<PublicKey, AccessKeyView>,
+}
 
+/// A cheaply cloneable handle; clones share the connection, the signer and the access key cache,
+/// so one account can serve concurrent calls without reusing a nonce.
+#[derive(Clone)]
 pub struct Account {
     connection: Connection,
     account_id: AccountId,
-    access_key_cache: HashMap<PublicKey, AccessKeyView>,
+    signing: Arc<Mutex<SigningState>>,
 }
 
 impl Account {
+    /// Signs with the connection's signer until `use_key_store` or `rotate_key` replace it.
     pub fn new(connection: Connection, account_id: AccountId) -> Self {
+        let signing = SigningState {
+            signer: connection.signer.clone(),
+            access_key_cache: HashMap::new(),
+        };
+
         Self {
             connection,
             account_id,
-            access_key_cache: HashMap::new(),
+            signing: Arc::new(Mutex::new(signing)),
         }
     }
 
-    pub async fn state(self) -> Result<QueryResponseKind, RpcError> {
-        let value = json!({
-            "request_type": "view_account",
-            "account_id": self.account_id,
-            "finality": "optimistic",
-        });
+    fn signer(&self) -> Result<Arc<dyn Signer>, AccountError> {
+        self.signing()
+            .signer
+            .clone()
+            .ok_or_else(|| AccountError::ReadOnly(self.account_id.clone()))
+    }
 
-        let params = match RpcQueryRequest::parse(Some(value)) {
-            Ok(reqest) => reqest,
-            Err(_) => panic!("Invalid value"),
-        };
+    fn signing(&self) -> MutexGuard<'_, SigningState> {
+        // The state holds no invariants that a panicking holder could break.
+        self.signing.lock().unwrap_or_else(PoisonError::into_inner)
+    }
 
-        self.connection.provider.query(params).await
+    pub fn account_id(&self) -> &AccountId {
+        &self.account_id
     }
 
-    fn _print_logs_and_failures(contract_id: &str, results: Vec<ReceiptLogWithFailure>) {
-        for result in results {
-            let receipt_log = match result.receipt_ids.len() {
-                1 => format!("Recept: {}", result.receipt_ids[0]),
-                _ => format!("Recepts: {}", result.receipt_ids.join(", ")),
-            };
-            log::info!("{}", receipt_log);
-            Self::_print_logs(contract_id, result.logs, None);
-            if result.failure.is_some() {
-                log::warn!("\tFailure [{}]: {:?}", contract_id, result.failure.unwrap());
-            }
-        }
+    /// The connection keeps the signer the account was created with.
+    pub fn connection(&self) -> &Connection {
+        &self.connection
     }
 
-    fn _print_logs(contract_id: &str, logs: Vec<String>, prefix: Option<String>) {
-        let prefoix = prefix.unwrap_or("".to_string());
+    pub async fn state(&self) -> Result<AccountView, AccountError> {
+        let request = QueryRequest::ViewAccount {
+            account_id: self.account_id.clone(),
+        };
 
-        for log_entry in logs {
-            log::info!("{}Log [{}]: {}", prefoix, contract_id, log_entry);
+        match self.query(request).await? {
+            QueryResponseKind::ViewAccount(account) => Ok(account),
+            _ => Err(unexpected_query_response()),
         }
     }
 
-    pub async fn sign_transaction(&mut self, receiver_id: AccountId, actions: Vec<Action>) {
-        let access_key_info = self.find_access_key(receiver_id, actions).await;
-        let access_key_info = match access_key_info {
-            Ok(access_key_info) => access_key_info,
-            Err(_) => panic!(
-                "Can not sign transactions for account {} on network {}, no matching key pair found in {}.",
-                 self.account_id,
-                 self.connection.network_id,
-                 self.connection.signer.public_key().to_string()
-            )
-        };
+    pub async fn sign_transaction(
+        &self,
+        receiver_id: AccountId,
+        actions: Vec<Action>,
+    ) -> Result<SignedTransaction, AccountError> {
+        // Sign with the key the nonce was taken for, even if the signer is replaced meanwhile.
+        self.sign_transaction_with(self.signer()?, receiver_id, actions)
+            .await
+    }
 
-        let access_key = access_key_info.access_key;
+    async fn sign_transaction_with(
+        &self,
+        signer: Arc<dyn Signer>,
+        receiver_id: AccountId,
+        actions: Vec<Action>,
+    ) -> Result<SignedTransaction, AccountError> {
+        let access_key_info = self.access_key_info(signer.public_key()).await?;
+        let public_key = access_key_info.public_key;
         let block_query = BlockReference::Finality(Finality::Final);
-        let block = self.connection.provider.block(block_query).await;
-        let block_hash = match block {
-            Ok(block) => block.header.hash,
-            _ => panic!("Fail to get block info"),
+        let block = self.connection.provider.block(block_query).await?;
+        // Reserve the nonce under the lock so that concurrent calls never share one.
+        let nonce = {
+            let mut signing = self.signing();
+            let access_key = signing
+                .access_key_cache
+                .entry(public_key.clone())
+                .or_insert(access_key_info.access_key);
+            access_key.nonce += 1;
+            access_key.nonce
         };
 
-        let nouce = 1 + access_key.nonce;
+        let transaction = Transaction {
+            signer_id: self.account_id.clone(),
+            public_key: public_key.clone(),
+            nonce,
+            receiver_id,
+            block_hash: block.header.hash,
+            actions,
+        };
+        let (hash, _) = transaction.get_hash_and_size();
+        let signature = signer.sign(hash.as_ref());
+
+        if !signature.verify(hash.as_ref(), &public_key) {
+            return Err(AccountError::Signing(format!(
+                "signer produced an invalid signature for {} with key {}",
+                hash, public_key
+            )));
+        }
 
-        // TODO:
-        // Transaction::sign_transaction(receiver_id, actions).await
+        Ok(SignedTransaction::new(signature, transaction))
     }
 
-    pub async fn sign_and_send_transaction() {
-        todo!();
+    /// Signs and sends the actions, returning an error if any receipt fails on chain.
+    pub async fn sign_and_send_transaction(
+        &self,
+        receiver_id: AccountId,
+        actions: Vec<Action>,
+    ) -> Result<FinalExecutionOutcome, AccountError> {
+        self.sign_and_send_transaction_with(self.signer()?, receiver_id, actions)
+            .await
     }
 
-    pub async fn find_access_key(
-        &mut self,
+    async fn sign_and_send_transaction_with(
+        &self,
+        signer: Arc<dyn Signer>,
         receiver_id: AccountId,
         actions: Vec<Action>,
-    ) -> Result<AccessKeyInfoView, Error> {
-        let public_key = self.connection.signer.public_key();
+    ) -> Result<FinalExecutionOutcome, AccountError> {
+        let signed_transaction = self
+            .sign_transaction_with(signer, receiver_id, actions)
+            .await?;
+        let public_key = signed_transaction.transaction.public_key.clone();
+        let outcome = match self
+            .connection
+            .provider
+            .send_transaction(signed_transaction)
+            .await
+        {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                // The nonce may or may not have been used, so fetch it again next time.
+                self.signing().access_key_cache.remove(&public_key);
+                return Err(err.into());
+            }
+        };
+
+        log::debug!("{}", ReceiptTree::new(&outcome));
+
+        if let Err(failure) = check_outcome(&outcome) {
+            // A rejected transaction, e.g. one with a stale nonce, leaves the cached nonce unknown.
+            if failure.receipt_id.is_none() {
+                self.signing().access_key_cache.remove(&public_key);
+            }
+            return Err(failure.into());
+        }
 
-        match self.access_key_cache.get(&public_key) {
-            Some(access_key_view) => Ok(AccessKeyInfoView {
+        Ok(outcome)
+    }
+
+    /// The access key the account currently signs with.
+    pub async fn find_access_key(&self) -> Result<AccessKeyInfoView, AccountError> {
+        self.access_key_info(self.signer()?.public_key()).await
+    }
+
+    async fn access_key_info(
+        &self,
+        public_key: PublicKey,
+    ) -> Result<AccessKeyInfoView, AccountError> {
+        let cached = self.signing().access_key_cache.get(&public_key).cloned();
+
+        if let Some(access_key_view) = cached {
+            return Ok(AccessKeyInfoView {
                 public_key,
-                access_key: access_key_view.clone(),
-            }),
+                access_key: access_key_view,
+            });
+        }
+
+        let access_key = match self.view_access_key(&public_key).await? {
+            Some(access_key) => access_key,
             None => {
-                let value = json!({
-                    "request_type": "view_access_key",
-                    "account_id": self.account_id,
-                    "public_key": public_key,
-                    "finality": "optimistic",
-                });
-
-                let params = match RpcQueryRequest::parse(Some(value)) {
-                    Ok(reqest) => reqest,
-                    Err(_) => panic!("Invalid value"),
-                };
-
-                let access_key_query_result = self.connection.provider.query(params).await;
-                let response_kind = match access_key_query_result {
-                    Ok(response) => response,
-                    Err(_) => panic!("Fail to the get access key"),
-                };
-                let access_key = match response_kind {
-                    QueryResponseKind::AccessKey(access_key_view) => access_key_view,
-                    _ => panic!("Fail to the get access key"),
-                };
-
-                self.access_key_cache
-                    .insert(public_key.clone(), access_key.clone());
-
-                Ok(AccessKeyInfoView {
-                    access_key,
+                return Err(AccountError::NoMatchingKey {
+                    account_id: self.account_id.clone(),
+                    network_id: self.connection.network_id.clone(),
                     public_key,
                 })
             }
-        }
+        };
+
+        self.signing().access_key_cache.insert(public_key.clone(), access_key.clone());
+
+        Ok(AccessKeyInfoView {
+            access_key,
+            public_key,
+        })
     }
 
+    /// Creates, funds and deploys `contract_id` in a single transaction, optionally
+    /// initializing it in the same batch.
     pub async fn create_and_deploy_contract(
+        &self,
         contract_id: AccountId,
         public_key: PublicKey,
-        data: Vec<u8>,
+        code: Vec<u8>,
         amount: Balance,
-    ) -> Account {
-        todo!();
+        init: Option<ContractInitOptions>,
+    ) -> Result<FinalExecutionOutcome, AccountError> {
+        let mut actions = vec![
+            Action::CreateAccount(CreateAccountAction {}),
+            Action::Transfer(TransferAction { deposit: amount }),
+            Action::AddKey(AddKeyAction {
+                public_key,
+                access_key: full_access_key(),
+            }),
+            Action::DeployContract(DeployContractAction { code }),
+        ];
+        actions.extend(init.map(Self::init_action));
+
+        self.sign_and_send_transaction(contract_id, actions).await
     }
 
-    pub async fn send_money(receiver_id: AccountId, amount: Balance) -> Account {
-        todo!();
+    pub async fn send_money(
+        &self,
+        receiver_id: AccountId,
+        amount: Balance,
+    ) -> Result<FinalExecutionOutcome, AccountError> {
+        let actions = vec![Action::Transfer(TransferAction { deposit: amount })];
+
+        self.sign_and_send_transaction(receiver_id, actions).await
     }
 
     pub async fn create_account(
-        self,
-        new_account_id: &str,
+        &self,
+        new_account_id: AccountId,
         public_key: PublicKey,
         amount: Balance,
-    ) -> FinalExecutionOutcome {
-        todo!();
+    ) -> Result<FinalExecutionOutcome, AccountError> {
+        let actions = vec![
+            Action::CreateAccount(CreateAccountAction {}),
+            Action::Transfer(TransferAction { deposit: amount }),
+            Action::AddKey(AddKeyAction {
+                public_key,
+                access_key: full_access_key(),
+            }),
+        ];
+
+        self.sign_and_send_transaction(new_account_id, actions).await
     }
 
-    pub async fn delete_account(beneficiary_id: AccountId) -> FinalExecutionOutcome {
-        todo!();
+    pub async fn delete_account(
+        &self,
+        beneficiary_id: AccountId,
+    ) -> Result<FinalExecutionOutcome, AccountError> {
+        let actions = vec![Action::DeleteAccount(DeleteAccountAction { beneficiary_id })];
+
+        self.sign_and_send_transaction(self.account_id.clone(), actions).await
+    }
+
+    /// Deploys `code` to this account, optionally initializing it in the same batch.
+    pub async fn deploy_contract(
+        &self,
+        code: Vec<u8>,
+        init: Option<ContractInitOptions>,
+    ) -> Result<FinalExecutionOutcome, AccountError> {
+        let mut actions = vec![Action::DeployContract(DeployContractAction { code })];
+        actions.extend(init.map(Self::init_action));
+
+        self.sign_and_send_transaction(self.account_id.clone(), actions).await
     }
 
-    pub async fn deploy_contract(data: Vec<u8>) -> FinalExecutionOutcome {
-        todo!();
+    fn init_action(init: ContractInitOptions) -> Action {
+        Action::FunctionCall(FunctionCallAction {
+            method_name: init.method_name,
+            args: init.args.to_string().into_bytes(),
+            gas: init.gas.unwrap_or(DEFAULT_FUNCTION_CALL_GAS),
+            deposit: init.attached_deposit.unwrap_or(0),
+        })
     }
 
-    pub async fn function_call(props: FunctionCallOptions) -> FinalExecutionOutcome {
-        todo!();
+    pub async fn function_call(
+        &self,
+        props: FunctionCallOptions,
+    ) -> Result<FinalExecutionOutcome, AccountError> {
+        let actions = vec![Action::FunctionCall(FunctionCallAction {
+            method_name: props.method_name,
+            args: props.args.to_string().into_bytes(),
+            gas: props.gas.unwrap_or(DEFAULT_FUNCTION_CALL_GAS),
+            deposit: props.attached_deposit.unwrap_or(0),
+        })];
+
+        self.sign_and_send_transaction(props.contract_id, actions).await
     }
 
+    /// Adds a full access key, or a function call key when `contract_id` is given. An
+    /// `allowance` of `None` makes the function call key unlimited.
     pub async fn add_key(
+        &self,
         public_key: PublicKey,
         contract_id: Option<AccountId>,
         method_names: Vec<String>,
+        allowance: Option<Balance>,
+    ) -> Result<FinalExecutionOutcome, AccountError> {
+        let access_key = match contract_id {
+            Some(contract_id) => {
+                function_call_access_key(contract_id.to_string(), method_names, allowance)
+            }
+            None => full_access_key(),
+        };
+        let actions = vec![Action::AddKey(AddKeyAction {
+            public_key: public_key.clone(),
+            access_key,
+        })];
+        let outcome = self
+            .sign_and_send_transaction(self.account_id.clone(), actions)
+            .await?;
+
+        // The nonce the chain gives a new key is only known once it is queried.
+        self.signing().access_key_cache.remove(&public_key);
+
+        Ok(outcome)
+    }
+
+    pub async fn delete_key(
+        &self,
+        public_key: PublicKey,
+    ) -> Result<FinalExecutionOutcome, AccountError> {
+        let actions = vec![Action::DeleteKey(DeleteKeyAction {
+            public_key: public_key.clone(),
+        })];
+        let outcome = self
+            .sign_and_send_transaction(self.account_id.clone(), actions)
+            .await?;
+
+        self.signing().access_key_cache.remove(&public_key);
+
+        Ok(outcome)
+    }
+
+    /// Replaces the account's default key in `key_store` with a fresh full access key. Both keys
+    /// stay in the store's rotation entry until the rotation finishes, so an interrupted rotation
+    /// resumes where it stopped and the store always holds a key that is on chain. The old key
+    /// signs the new one in and the new key deletes the old one; clones of this account keep
+    /// their signer until the rotation has finished and then sign with the new key.
+    pub async fn rotate_key<K: AsyncKeyStore + ?Sized>(
+        &self,
+        key_store: &mut K,
+    ) -> Result<KeyRotationReport, AccountError> {
+        let network_id = self.connection.network_id.clone();
+        let [old_key, new_key] =
+            load_rotation_keys(key_store, &self.account_id, &network_id).await?;
+        let mut report = KeyRotationReport {
+            old_public_key: old_key.public_key(),
+            new_public_key: new_key.public_key(),
+            add_key_transaction: None,
+            delete_key_transaction: None,
+        };
+
+        if self.view_access_key(&report.new_public_key).await?.is_none() {
+            let actions = vec![Action::AddKey(AddKeyAction {
+                public_key: report.new_public_key.clone(),
+                access_key: full_access_key(),
+            })];
+            let outcome = self
+                .sign_and_send_transaction_with(
+                    self.key_signer(old_key),
+                    self.account_id.clone(),
+                    actions,
+                )
+                .await?;
+            report.add_key_transaction = Some(outcome.transaction_outcome.id);
+        }
+
+        promote_rotated_key(
+            key_store,
+            &self.account_id,
+            &network_id,
+            &report.old_public_key,
+            new_key.clone(),
+        )
+        .await?;
+
+        if self.view_access_key(&report.old_public_key).await?.is_some() {
+            let actions = vec![Action::DeleteKey(DeleteKeyAction {
+                public_key: report.old_public_key.clone(),
+            })];
+            let outcome = self
+                .sign_and_send_transaction_with(
+                    self.key_signer(new_key.clone()),
+                    self.account_id.clone(),
+                    actions,
+                )
+                .await?;
+            report.delete_key_transaction = Some(outcome.transaction_outcome.id);
+        }
+
+        key_store
+            .set_rotation_keys_async(&self.account_id, &network_id, vec![])
+            .await?;
+        self.signing().access_key_cache.remove(&report.old_public_key);
+        self.set_signer(new_key);
+
+        Ok(report)
+    }
+
+    /// Signs with the key in `key_store` that matches one of the account's access keys on
+    /// chain, preferring full access keys. Clones of this account sign with it too.
+    pub async fn use_key_store<K: AsyncKeyStore + ?Sized>(
+        &self,
+        key_store: &K,
+    ) -> Result<(), AccountError> {
+        let access_keys = self.get_access_keys().await?;
+        let key_pair = key_store
+            .find_matching_key_async(&self.account_id, &self.connection.network_id, &access_keys)
+            .await?
+            .ok_or_else(|| AccountError::MissingKey {
+                account_id: self.account_id.clone(),
+                network_id: self.connection.network_id.clone(),
+            })?;
+
+        self.set_signer(key_pair);
+        Ok(())
+    }
+
+    fn set_signer(&self, secret_key: SecretKey) {
+        let signer = self.key_signer(secret_key);
+
+        self.signing().signer = Some(signer);
+    }
+
+    fn key_signer(&self, secret_key: SecretKey) -> Arc<dyn Signer> {
+        Arc::new(InMemorySigner::from_secret_key(self.account_id.clone(), secret_key))
+    }
+
+    /// Stakes `amount` with `public_key` as the validator key. Staking `0` unstakes.
+    pub async fn stake(
+        &self,
+        public_key: PublicKey,
         amount: Balance,
-    ) -> FinalExecutionOutcome {
-        todo!();
+    ) -> Result<FinalExecutionOutcome, AccountError> {
+        let actions = vec![Action::Stake(StakeAction {
+            stake: amount,
+            public_key,
+        })];
+
+        self.sign_and_send_transaction(self.account_id.clone(), actions).await
     }
 
-    pub async fn delete_key(public_key: PublicKey) -> FinalExecutionOutcome {
-        todo!();
+    /// Calls a view method and decodes its JSON result into `T`.
+    pub async fn view_function<T: DeserializeOwned>(
+        &self,
+        contract_id: AccountId,
+        method_name: String,
+        args: Value,
+    ) -> Result<T, AccountError> {
+        let call_result = self.view_function_raw(contract_id, method_name, args).await?;
+
+        serde_json::from_slice(&call_result.result)
+            .map_err(|err| AccountError::Decode(err.to_string()))
     }
 
-    pub async fn stake(public_key: PublicKey, amount: Balance) -> FinalExecutionOutcome {
-        todo!();
+    /// Calls a view method and decodes its Borsh result into `T`.
+    pub async fn view_function_borsh<T: BorshDeserialize>(
+        &self,
+        contract_id: AccountId,
+        method_name: String,
+        args: Value,
+    ) -> Result<T, AccountError> {
+        let call_result = self.view_function_raw(contract_id, method_name, args).await?;
+
+        T::try_from_slice(&call_result.result).map_err(|err| AccountError::Decode(err.to_string()))
     }
 
-    pub async fn view_function(
+    /// Calls a view method and returns the raw result bytes along with the contract logs.
+    pub async fn view_function_raw(
+        &self,
         contract_id: AccountId,
         method_name: String,
         args: Value,
-    ) -> FinalExecutionOutcome {
-        todo!();
+    ) -> Result<CallResult, AccountError> {
+        let request = QueryRequest::CallFunction {
+            account_id: contract_id.clone(),
+            method_name: method_name.clone(),
+            args: args.to_string().into_bytes().into(),
+        };
+        let response = match self.query(request).await {
+            Ok(response) => response,
+            Err(AccountError::Rpc(err)) => {
+                return Err(match query_error(&err) {
+                    Some(RpcQueryError::ContractExecutionError { vm_error, .. }) => {
+                        AccountError::ContractExecution {
+                            contract_id,
+                            method_name,
+                            vm_error,
+                        }
+                    }
+                    _ => AccountError::Rpc(err),
+                })
+            }
+            Err(err) => return Err(err),
+        };
+        let call_result = match response {
+            QueryResponseKind::CallResult(call_result) => call_result,
+            _ => return Err(unexpected_query_response()),
+        };
+
+        for log_entry in &call_result.logs {
+            log::info!("Log [{}]: {}", contract_id, log_entry);
+        }
+
+        Ok(call_result)
+    }
+
+    /// Returns the contract state under `prefix` as decoded key/value pairs.
+    pub async fn view_state(
+        &self,
+        prefix: &[u8],
+        block_query: BlockReference,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, AccountError> {
+        let request = QueryRequest::ViewState {
+            account_id: self.account_id.clone(),
+            prefix: prefix.to_vec().into(),
+        };
+        let view_state = match self.query_at(request, block_query).await? {
+            QueryResponseKind::ViewState(view_state) => view_state,
+            _ => return Err(unexpected_query_response()),
+        };
+
+        view_state
+            .values
+            .into_iter()
+            .map(|item| {
+                let key = base64::decode(&item.key);
+                let value = base64::decode(&item.value);
+                match (key, value) {
+                    (Ok(key), Ok(value)) => Ok((key, value)),
+                    (Err(err), _) | (_, Err(err)) => Err(AccountError::Decode(err.to_string())),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns `None` when the account has no such key.
+    pub async fn view_access_key(
+        &self,
+        public_key: &PublicKey,
+    ) -> Result<Option<AccessKeyView>, AccountError> {
+        let request = QueryRequest::ViewAccessKey {
+            account_id: self.account_id.clone(),
+            public_key: public_key.clone(),
+        };
+
+        match self.query(request).await {
+            Ok(QueryResponseKind::AccessKey(access_key_view)) => Ok(Some(access_key_view)),
+            Ok(_) => Err(unexpected_query_response()),
+            Err(AccountError::Rpc(err))
+                if matches!(query_error(&err), Some(RpcQueryError::UnknownAccessKey { .. })) =>
+            {
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
     }
 
-    pub async fn view_state(prefix: &str, block_query: BlockReference, args: Value) {
-        todo!();
+    pub async fn get_access_keys(&self) -> Result<Vec<AccessKeyInfoView>, AccountError> {
+        let request = QueryRequest::ViewAccessKeyList {
+            account_id: self.account_id.clone(),
+        };
+        let keys = match self.query(request).await? {
+            QueryResponseKind::AccessKeyList(access_key_list) => access_key_list.keys,
+            _ => return Err(unexpected_query_response()),
+        };
+
+        // Keep the cached keys in step with the chain, without going back on a nonce that a
+        // transaction in flight may already have used.
+        let mut signing = self.signing();
+        let access_key_cache = &mut signing.access_key_cache;
+        access_key_cache
+            .retain(|public_key, _| keys.iter().any(|key| &key.public_key == public_key));
+        for key in &keys {
+            let cached = access_key_cache
+                .entry(key.public_key.clone())
+                .or_insert_with(|| key.access_key.clone());
+            cached.nonce = cached.nonce.max(key.access_key.nonce);
+            cached.permission = key.access_key.permission.clone();
+        }
+
+        Ok(keys)
     }
 
-    pub async fn get_access_keys() -> Vec<AccessKeyInfoView> {
-        todo!();
+    async fn query(&self, request: QueryRequest) -> Result<QueryResponseKind, AccountError> {
+        self.query_at(request, BlockReference::latest()).await
     }
 
-    pub async fn get_account_details() -> Vec<AccountAuthorizedApp> {
-        todo!();
+    async fn query_at(
+        &self,
+        request: QueryRequest,
+        block_reference: BlockReference,
+    ) -> Result<QueryResponseKind, AccountError> {
+        let params = RpcQueryRequest {
+            block_reference,
+            request,
+        };
+
+        match self.connection.provider.query(params).await {
+            Ok(response) => Ok(response),
+            Err(err) => match query_error(&err) {
+                Some(RpcQueryError::UnknownAccount {
+                    requested_account_id,
+                    ..
+                }) => Err(AccountError::UnknownAccount(requested_account_id)),
+                _ => Err(err.into()),
+            },
+        }
+    }
+
+    /// Lists every function call key grouped by contract, each group sorted by public key.
+    /// Contracts are keyed by the receiver as stored on chain, so keys whose receiver is not a
+    /// valid account id are listed too.
+    pub async fn get_account_details(
+        &self,
+    ) -> Result<BTreeMap<String, Vec<AccountAuthorizedApp>>, AccountError> {
+        let mut authorized_apps = BTreeMap::<_, Vec<_>>::new();
+
+        for key in self.get_access_keys().await? {
+            if let AccessKeyPermissionView::FunctionCall {
+                allowance,
+                receiver_id,
+                ..
+            } = key.access_key.permission
+            {
+                authorized_apps
+                    .entry(receiver_id.clone())
+                    .or_default()
+                    .push(AccountAuthorizedApp {
+                        contract_id: receiver_id,
+                        amount: allowance,
+                        public_key: key.public_key,
+                    });
+            }
+        }
+        for apps in authorized_apps.values_mut() {
+            apps.sort_by(|a, b| a.public_key.cmp(&b.public_key));
+        }
+
+        Ok(authorized_apps)
+    }
+
+    /// Classifies every access key on chain and cross-checks them with the keys `key_store`
+    /// holds for this account, including those of an unfinished key rotation. Function call keys
+    /// with an allowance below `low_allowance` are flagged.
+    pub async fn audit_access_keys<K: AsyncKeyStore + ?Sized>(
+        &self,
+        key_store: &K,
+        low_allowance: Balance,
+    ) -> Result<AccessKeyAudit, AccountError> {
+        let network_id = &self.connection.network_id;
+        let mut local_keys = vec![];
+        for key_pair in key_store
+            .get_keys_async(&self.account_id, network_id)
+            .await?
+            .into_iter()
+            .chain(key_store.get_rotation_keys_async(&self.account_id, network_id).await?)
+        {
+            if !local_keys.contains(&key_pair.public_key()) {
+                local_keys.push(key_pair.public_key());
+            }
+        }
+        let mut contract_exists = HashMap::new();
+        let mut keys = vec![];
+
+        for key in self.get_access_keys().await? {
+            let mut risks = vec![];
+
+            match &key.access_key.permission {
+                AccessKeyPermissionView::FullAccess => risks.push(AccessKeyRisk::FullAccess),
+                AccessKeyPermissionView::FunctionCall {
+                    allowance,
+                    receiver_id,
+                    ..
+                } => {
+                    match allowance {
+                        None => risks.push(AccessKeyRisk::UnlimitedAllowance),
+                        Some(allowance) if *allowance < low_allowance => {
+                            risks.push(AccessKeyRisk::LowAllowance)
+                        }
+                        Some(_) => {}
+                    }
+
+                    let exists = match contract_exists.get(receiver_id) {
+                        Some(exists) => *exists,
+                        None => {
+                            let exists = self.contract_exists(receiver_id).await?;
+                            contract_exists.insert(receiver_id.clone(), exists);
+                            exists
+                        }
+                    };
+                    if !exists {
+                        risks.push(AccessKeyRisk::MissingContract);
+                    }
+                }
+            }
+
+            keys.push(AccessKeyAuditEntry {
+                in_key_store: local_keys.contains(&key.public_key),
+                public_key: key.public_key,
+                permission: key.access_key.permission,
+                risks,
+            });
+        }
+
+        let missing_on_chain = local_keys
+            .into_iter()
+            .filter(|public_key| keys.iter().all(|key| &key.public_key != public_key))
+            .collect();
+
+        Ok(AccessKeyAudit {
+            account_id: self.account_id.clone(),
+            network_id: network_id.clone(),
+            keys,
+            missing_on_chain,
+        })
+    }
+
+    async fn contract_exists(&self, contract_id: &str) -> Result<bool, AccountError> {
+        let contract_id = match contract_id.parse() {
+            Ok(contract_id) => contract_id,
+            Err(_) => return Ok(false),
+        };
+
+        match Account::new(self.connection.clone(), contract_id).state().await {
+            Ok(account) => Ok(account.code_hash != CryptoHash::default()),
+            Err(AccountError::UnknownAccount(_)) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Deletes every function call key for `contract_id` in one transaction. Returns `None` when
+    /// there is nothing to delete.
+    pub async fn revoke_authorized_app(
+        &self,
+        contract_id: &str,
+    ) -> Result<Option<FinalExecutionOutcome>, AccountError> {
+        let public_keys = self
+            .get_account_details()
+            .await?
+            .remove(contract_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|authorized_app| authorized_app.public_key)
+            .collect::<Vec<_>>();
+
+        if public_keys.is_empty() {
+            return Ok(None);
+        }
+
+        let actions = public_keys
+            .iter()
+            .map(|public_key| {
+                Action::DeleteKey(DeleteKeyAction {
+                    public_key: public_key.clone(),
+                })
+            })
+            .collect();
+        let outcome = self
+            .sign_and_send_transaction(self.account_id.clone(), actions)
+            .await?;
+
+        let mut signing = self.signing();
+        for public_key in &public_keys {
+            signing.access_key_cache.remove(public_key);
+        }
+
+        Ok(Some(outcome))
+    }
+
+    /// Splits the balance the way the runtime sees it: `available` is what can be spent without
+    /// the transfer failing with `LackBalanceForState`.
+    pub async fn get_account_balance(&self) -> Result<AccountBalance, AccountError> {
+        let request = QueryRequest::ViewAccount {
+            account_id: self.account_id.clone(),
+        };
+        let account = match self.query(request).await? {
+            QueryResponseKind::ViewAccount(account) => account,
+            _ => return Err(unexpected_query_response()),
+        };
+        let protocol_config = self
+            .connection
+            .provider
+            .experimental_protocol_config(BlockReference::Finality(Finality::Final))
+            .await?;
+
+        let storage_amount_per_byte = protocol_config.runtime_config.storage_amount_per_byte;
+        let state_staked = Balance::from(account.storage_usage) * storage_amount_per_byte;
+        let staked = account.locked;
+        let total = account.amount + account.locked;
+        // Locked tokens count towards storage staking, but can never be spent themselves.
+        let available = total.saturating_sub(staked.max(state_staked));
+
+        Ok(AccountBalance {
+            total,
+            state_staked,
+            staked,
+            available,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use crate::key_stores::{InMemKeyStore, KeyStore};
+
+    use super::{
+        load_rotation_keys, promote_rotated_key, AccountError, AccountId, KeyType, SecretKey,
+    };
+
+    const NETWORK_ID: &str = "testnet";
+
+    fn account_id() -> AccountId {
+        "alice.testnet".parse().unwrap()
+    }
+
+    #[test]
+    fn starts_a_rotation_from_the_default_key() {
+        let key_pair = SecretKey::from_random(KeyType::ED25519);
+        let mut key_store = InMemKeyStore::new();
+        key_store
+            .set_keys(&account_id(), NETWORK_ID, vec![key_pair.clone()])
+            .unwrap();
+
+        let [old_key, new_key] =
+            block_on(load_rotation_keys(&mut key_store, &account_id(), NETWORK_ID)).unwrap();
+
+        assert_eq!(old_key, key_pair);
+        assert_ne!(new_key, key_pair);
+        assert_eq!(
+            key_store.get_rotation_keys(&account_id(), NETWORK_ID).unwrap(),
+            vec![old_key, new_key]
+        );
+        // The new key only becomes the default once it is on chain.
+        assert_eq!(
+            key_store.get_keys(&account_id(), NETWORK_ID).unwrap(),
+            vec![key_pair]
+        );
+    }
+
+    #[test]
+    fn resumes_a_rotation_from_persisted_keys() {
+        let old_key = SecretKey::from_random(KeyType::ED25519);
+        let new_key = SecretKey::from_random(KeyType::ED25519);
+        let other_key = SecretKey::from_random(KeyType::ED25519);
+        let mut key_store = InMemKeyStore::new();
+        key_store
+            .set_keys(&account_id(), NETWORK_ID, vec![old_key.clone(), other_key.clone()])
+            .unwrap();
+        key_store
+            .set_rotation_keys(&account_id(), NETWORK_ID, vec![old_key.clone(), new_key.clone()])
+            .unwrap();
+
+        let rotation_keys =
+            block_on(load_rotation_keys(&mut key_store, &account_id(), NETWORK_ID)).unwrap();
+        assert_eq!(rotation_keys, [old_key.clone(), new_key.clone()]);
+
+        // Promoting twice, as a rotation interrupted after the first promotion does, is harmless.
+        for _ in 0..2 {
+            block_on(promote_rotated_key(
+                &mut key_store,
+                &account_id(),
+                NETWORK_ID,
+                &old_key.public_key(),
+                new_key.clone(),
+            ))
+            .unwrap();
+            assert_eq!(
+                key_store.get_keys(&account_id(), NETWORK_ID).unwrap(),
+                vec![new_key.clone(), other_key.clone()]
+            );
+        }
+
+        // Resuming after the promotion still finds the old key to delete on chain.
+        let rotation_keys =
+            block_on(load_rotation_keys(&mut key_store, &account_id(), NETWORK_ID)).unwrap();
+        assert_eq!(rotation_keys, [old_key, new_key]);
     }
 
-    pub async fn get_account_balance() -> AccountBalance {
-        todo!();
+    #[test]
+    fn rotation_needs_a_key() {
+        let mut key_store = InMemKeyStore::new();
+
+        assert!(matches!(
+            block_on(load_rotation_keys(&mut key_store, &account_id(), NETWORK_ID)),
+            Err(AccountError::MissingKey { .. })
+        ));
+        assert!(key_store
+            .get_rotation_keys(&account_id(), NETWORK_ID)
+            .unwrap()
+            .is_empty());
     }
 }
@@ -1,50 +1,197 @@
+use async_trait::async_trait;
+use near_account_id::AccountId;
 use near_crypto::PublicKey;
-use near_primitives::types::Balance;
+use near_primitives::{
+    types::{Balance, Gas},
+    views::{ExecutionStatusView, FinalExecutionStatus},
+};
 
-use crate::{fetch_json, Connection};
+use crate::{
+    fetch,
+    outcome::{check_outcome, ExecutionFailureKind, ReceiptTree},
+    provider::types::FinalExecutionOutcome,
+};
 
-use super::account::Account;
+use super::{account::Account, AccountError, FunctionCallOptions};
 
+const REGISTRAR_CREATE_ACCOUNT_GAS: Gas = 100_000_000_000_000;
+
+#[async_trait(?Send)]
+pub trait AccountCreator {
+    async fn create_account(
+        &self,
+        new_account_id: AccountId,
+        public_key: PublicKey,
+    ) -> Result<FinalExecutionOutcome, AccountError>;
+}
+
+/// Creates sub-accounts of `master_account`, funded with `initial_balance`.
 pub struct LocalAccountCreator {
     master_account: Account,
-    intial_balance: Balance,
+    initial_balance: Balance,
 }
+
 impl LocalAccountCreator {
-    pub fn new(master_account: Account, intial_balance: Balance) -> Self {
+    pub fn new(master_account: Account, initial_balance: Balance) -> Self {
         Self {
             master_account,
-            intial_balance,
+            initial_balance,
         }
     }
-    async fn create_account(self, new_account_id: &str, public_key: PublicKey) {
+}
+
+#[async_trait(?Send)]
+impl AccountCreator for LocalAccountCreator {
+    async fn create_account(
+        &self,
+        new_account_id: AccountId,
+        public_key: PublicKey,
+    ) -> Result<FinalExecutionOutcome, AccountError> {
         self.master_account
-            .create_account(new_account_id, public_key, self.intial_balance)
-            .await;
+            .create_account(new_account_id, public_key, self.initial_balance)
+            .await
     }
 }
 
+/// Creates accounts through a contract helper service.
 pub struct UrlAccountCreator {
-    connection: Connection,
     helper_url: String,
 }
+
 impl UrlAccountCreator {
-    pub fn new(connection: Connection, helper_url: String) -> Self {
-        Self {
-            connection,
-            helper_url,
-        }
+    pub fn new(helper_url: String) -> Self {
+        Self { helper_url }
     }
-    async fn create_account(self, new_account_id: &str, public_key: PublicKey) {
-        let url = format!("{}/account", self.helper_url);
+}
+
+#[async_trait(?Send)]
+impl AccountCreator for UrlAccountCreator {
+    async fn create_account(
+        &self,
+        new_account_id: AccountId,
+        public_key: PublicKey,
+    ) -> Result<FinalExecutionOutcome, AccountError> {
+        let url = format!("{}/account", self.helper_url.trim_end_matches('/'));
         let data = serde_json::json!({
             "newAccountId": new_account_id,
-            "newAccountPublicKey":  public_key.to_string(),
+            "newAccountPublicKey": public_key.to_string(),
         });
-        let response = fetch_json(&url, &data.to_string()).await;
+        let response = fetch(&url, &data.to_string())
+            .await
+            .map_err(|err| AccountError::Transport(format!("{:?}", err)))?;
+
+        if !response.ok() {
+            return Err(AccountError::Http {
+                status: response.status,
+                message: response.body,
+            });
+        }
+
+        let outcome: FinalExecutionOutcome = serde_json::from_str(&response.body)
+            .map_err(|err| AccountError::Decode(err.to_string()))?;
+
+        check_outcome(&outcome)?;
+        Ok(outcome)
+    }
+}
+
+/// Creates top-level accounts by calling `create_account` on the network's registrar contract,
+/// paid for by `funding_account`.
+pub struct RegistrarAccountCreator {
+    funding_account: Account,
+    registrar_id: AccountId,
+    initial_balance: Balance,
+}
+
+impl RegistrarAccountCreator {
+    pub fn new(
+        funding_account: Account,
+        registrar_id: AccountId,
+        initial_balance: Balance,
+    ) -> Self {
+        Self {
+            funding_account,
+            registrar_id,
+            initial_balance,
+        }
+    }
+
+    /// Uses `near` on mainnet and `testnet` on testnet; other networks have no known registrar.
+    pub fn for_network(
+        funding_account: Account,
+        network_id: &str,
+        initial_balance: Balance,
+    ) -> Option<Self> {
+        let registrar_id = match network_id {
+            "mainnet" => "near",
+            "testnet" => "testnet",
+            _ => return None,
+        };
+
+        Some(Self::new(funding_account, registrar_id.parse().ok()?, initial_balance))
+    }
+
+    /// The registrar reports success from its callback, so the receipt executed on the new
+    /// account is what tells whether it exists.
+    fn check_created(
+        new_account_id: &AccountId,
+        outcome: &FinalExecutionOutcome,
+    ) -> Result<(), AccountError> {
+        let tree = ReceiptTree::from(outcome);
+        let created = tree.nodes().iter().any(|node| {
+            &node.executor_id == new_account_id
+                && matches!(
+                    node.status,
+                    ExecutionStatusView::SuccessValue(_) | ExecutionStatusView::SuccessReceiptId(_)
+                )
+        });
+        let rejected = matches!(
+            &outcome.status,
+            FinalExecutionStatus::SuccessValue(value)
+                if base64::decode(value).map_or(false, |value| value == b"false")
+        );
+
+        if created && !rejected {
+            Ok(())
+        } else {
+            Err(AccountError::NotCreated(new_account_id.clone()))
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl AccountCreator for RegistrarAccountCreator {
+    async fn create_account(
+        &self,
+        new_account_id: AccountId,
+        public_key: PublicKey,
+    ) -> Result<FinalExecutionOutcome, AccountError> {
+        let result = self
+            .funding_account
+            .function_call(FunctionCallOptions {
+                contract_id: self.registrar_id.clone(),
+                method_name: "create_account".to_string(),
+                args: serde_json::json!({
+                    "new_account_id": new_account_id,
+                    "new_public_key": public_key.to_string(),
+                }),
+                gas: Some(REGISTRAR_CREATE_ACCOUNT_GAS),
+                attached_deposit: Some(self.initial_balance),
+                wallet_meta: None,
+                wallet_callback_url: None,
+            })
+            .await;
 
-        match response {
-            Ok(_) => log::info!("Success to create account: {}", new_account_id),
-            Err(_) => log::warn!("Fail to create account: {}", new_account_id),
+        match result {
+            Ok(outcome) => Self::check_created(&new_account_id, &outcome).map(|_| outcome),
+            Err(AccountError::Execution(failure))
+                if failure.executor_id == new_account_id
+                    && matches!(failure.kind, ExecutionFailureKind::Action(_)) =>
+            {
+                log::warn!("Fail to create account {}: {}", new_account_id, failure);
+                Err(AccountError::NotCreated(new_account_id))
+            }
+            Err(err) => Err(err),
         }
     }
 }
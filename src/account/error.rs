@@ -0,0 +1,61 @@
+use near_account_id::AccountId;
+use near_crypto::PublicKey;
+use near_jsonrpc_primitives::errors::RpcError;
+
+use crate::{key_stores::KeyStoreError, outcome::ExecutionFailure};
+
+use super::SeedPhraseError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AccountError {
+    #[error(
+        "Can not sign transactions for account {account_id} on network {network_id}, no matching \
+         key pair found in {public_key}."
+    )]
+    NoMatchingKey {
+        account_id: AccountId,
+        network_id: String,
+        public_key: PublicKey,
+    },
+    #[error("No key for account {account_id} on network {network_id} in the key store")]
+    MissingKey {
+        account_id: AccountId,
+        network_id: String,
+    },
+    #[error("Account {0} does not exist")]
+    UnknownAccount(AccountId),
+    #[error("Account {0} was not created")]
+    NotCreated(AccountId),
+    #[error("RPC error: {0}")]
+    Rpc(RpcError),
+    #[error("Account {0} is read-only, its connection has no signer")]
+    ReadOnly(AccountId),
+    #[error("HTTP error {status}: {message}")]
+    Http { status: u16, message: String },
+    #[error("Request failed: {0}")]
+    Transport(String),
+    #[error("Failed to sign transaction: {0}")]
+    Signing(String),
+    #[error(transparent)]
+    Execution(#[from] ExecutionFailure),
+    #[error("{contract_id}.{method_name} failed: {vm_error}")]
+    ContractExecution {
+        contract_id: AccountId,
+        method_name: String,
+        vm_error: String,
+    },
+    #[error(transparent)]
+    KeyStore(#[from] KeyStoreError),
+    #[error(transparent)]
+    SeedPhrase(#[from] SeedPhraseError),
+    #[error("Invalid client config: {0}")]
+    InvalidConfig(String),
+    #[error("Failed to decode result: {0}")]
+    Decode(String),
+}
+
+impl From<RpcError> for AccountError {
+    fn from(error: RpcError) -> Self {
+        Self::Rpc(error)
+    }
+}
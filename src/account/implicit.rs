@@ -0,0 +1,87 @@
+use near_account_id::AccountId;
+use near_crypto::{KeyType, PublicKey, SecretKey};
+
+use crate::key_stores::{KeyStore, KeyStoreError};
+
+/// The implicit account id of an ED25519 key: its 32 bytes as 64 lowercase hex characters.
+pub fn implicit_account_id(public_key: &PublicKey) -> Option<AccountId> {
+    match public_key {
+        PublicKey::ED25519(key) => {
+            let account_id = key.0.iter().map(|byte| format!("{:02x}", byte)).collect::<String>();
+
+            account_id.parse().ok()
+        }
+        _ => None,
+    }
+}
+
+pub fn is_implicit_account(account_id: &AccountId) -> bool {
+    let account_id = account_id.as_str();
+
+    account_id.len() == 64
+        && account_id
+            .bytes()
+            .all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Generates an ED25519 key and stores it under its implicit account id. The account exists
+/// on chain once the returned id receives a transfer.
+pub fn create_implicit_account(
+    key_store: &mut dyn KeyStore,
+    network_id: &str,
+) -> Result<AccountId, KeyStoreError> {
+    let secret_key = SecretKey::from_random(KeyType::ED25519);
+    let account_id = implicit_account_id(&secret_key.public_key())
+        .expect("ED25519 keys always have an implicit account id");
+
+    key_store.set_key(&account_id, network_id, secret_key)?;
+
+    Ok(account_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::ED25519PublicKey;
+
+    use crate::key_stores::InMemKeyStore;
+
+    use super::*;
+
+    #[test]
+    fn implicit_account_id_is_lowercase_hex() {
+        let mut bytes = [0u8; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = (i as u8) * 8;
+        }
+        let public_key = PublicKey::ED25519(ED25519PublicKey(bytes));
+        let account_id = implicit_account_id(&public_key).unwrap();
+
+        assert_eq!(
+            account_id.as_str(),
+            "0008101820283038404850586068707880889098a0a8b0b8c0c8d0d8e0e8f0f8"
+        );
+        assert!(is_implicit_account(&account_id));
+    }
+
+    #[test]
+    fn only_ed25519_keys_have_implicit_accounts() {
+        let secret_key = SecretKey::from_random(KeyType::SECP256K1);
+
+        assert_eq!(implicit_account_id(&secret_key.public_key()), None);
+    }
+
+    #[test]
+    fn named_accounts_are_not_implicit() {
+        assert!(!is_implicit_account(&"alice.near".parse().unwrap()));
+        assert!(!is_implicit_account(&"g".repeat(64).parse().unwrap()));
+    }
+
+    #[test]
+    fn create_implicit_account_stores_key() {
+        let mut key_store = InMemKeyStore::new();
+        let account_id = create_implicit_account(&mut key_store, "testnet").unwrap();
+        let secret_key = key_store.get_key(&account_id, "testnet").unwrap().unwrap();
+
+        assert_eq!(implicit_account_id(&secret_key.public_key()), Some(account_id));
+    }
+}
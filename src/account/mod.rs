@@ -1,13 +1,26 @@
 mod account;
 mod account_creator;
+mod error;
+mod implicit;
+mod seed_phrase;
 
-use account::*;
-use account_creator::*;
+pub use account::*;
+pub use account_creator::*;
+pub use error::*;
+pub use implicit::*;
+pub use seed_phrase::*;
 use near_account_id::AccountId;
 use near_crypto::PublicKey;
-use near_primitives::{types::Balance, views::ServerError};
+use near_primitives::{
+    types::{Balance, Gas},
+    views::AccessKeyPermissionView,
+};
 use serde::{Deserialize, Serialize};
 
+use crate::provider::types::CryptoHash;
+
+pub const DEFAULT_FUNCTION_CALL_GAS: Gas = 30_000_000_000_000;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AccountBalance {
     pub total: Balance,
@@ -18,8 +31,11 @@ pub struct AccountBalance {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AccountAuthorizedApp {
-    pub contract_id: AccountId,
-    pub amount: Balance,
+    /// The key's receiver as stored on chain, which older keys may hold in a form that is no
+    /// longer a valid account id.
+    pub contract_id: String,
+    /// Remaining allowance, or `None` if the key is unlimited.
+    pub amount: Option<Balance>,
     pub public_key: PublicKey,
 }
 
@@ -36,15 +52,54 @@ pub struct FunctionCallOptions {
     pub contract_id: AccountId,
     pub method_name: String,
     pub args: serde_json::Value,
-    pub gas: Option<Balance>,
+    pub gas: Option<Gas>,
     pub attached_deposit: Option<Balance>,
     pub wallet_meta: Option<String>,
     pub wallet_callback_url: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-pub struct ReceiptLogWithFailure {
-    pub receipt_ids: Vec<AccountId>,
-    pub logs: Vec<String>,
-    pub failure: Option<ServerError>,
+pub struct ContractInitOptions {
+    pub method_name: String,
+    pub args: serde_json::Value,
+    pub gas: Option<Gas>,
+    pub attached_deposit: Option<Balance>,
+}
+
+/// Outcome of `Account::rotate_key`. A transaction is `None` when an earlier, interrupted
+/// rotation already completed that step.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct KeyRotationReport {
+    pub old_public_key: PublicKey,
+    pub new_public_key: PublicKey,
+    pub add_key_transaction: Option<CryptoHash>,
+    pub delete_key_transaction: Option<CryptoHash>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessKeyRisk {
+    FullAccess,
+    UnlimitedAllowance,
+    LowAllowance,
+    /// The key's receiver does not exist or has no contract deployed.
+    MissingContract,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AccessKeyAuditEntry {
+    pub public_key: PublicKey,
+    pub permission: AccessKeyPermissionView,
+    pub risks: Vec<AccessKeyRisk>,
+    /// `false` for keys that are on chain but that nobody holds locally.
+    pub in_key_store: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AccessKeyAudit {
+    pub account_id: AccountId,
+    pub network_id: String,
+    pub keys: Vec<AccessKeyAuditEntry>,
+    /// Keys in the key store that the account does not have on chain.
+    pub missing_on_chain: Vec<PublicKey>,
 }
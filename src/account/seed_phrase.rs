@@ -0,0 +1,177 @@
+use bip39::Mnemonic;
+use hmac::{Hmac, Mac};
+use near_account_id::AccountId;
+use near_crypto::PublicKey;
+use serde::{Deserialize, Serialize};
+use sha2::Sha512;
+
+use crate::{key_stores::KeyPair, Connection};
+
+use super::{implicit_account_id, Account, AccountError};
+
+/// The path NEAR wallets derive their first key from.
+pub const DEFAULT_DERIVATION_PATH: &str = "m/44'/397'/0'";
+
+const HARDENED_OFFSET: u32 = 0x8000_0000;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SeedPhraseError {
+    #[error("Invalid seed phrase: {0}")]
+    InvalidPhrase(String),
+    #[error("Invalid derivation path {0}, ED25519 only supports hardened indexes")]
+    InvalidPath(String),
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+}
+
+/// The key a seed phrase derives to and the accounts that hold it as an access key.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SeedPhraseRecovery {
+    pub public_key: PublicKey,
+    pub account_ids: Vec<AccountId>,
+}
+
+/// Generates a BIP-39 mnemonic of 12, 15, 18, 21 or 24 English words.
+pub fn generate_seed_phrase(word_count: usize) -> Result<String, SeedPhraseError> {
+    Mnemonic::generate(word_count)
+        .map(|mnemonic| mnemonic.to_string())
+        .map_err(|err| SeedPhraseError::InvalidPhrase(err.to_string()))
+}
+
+/// Derives the ED25519 key of `seed_phrase` along `path` using SLIP-10, as NEAR wallets do.
+pub fn key_pair_from_seed_phrase(
+    seed_phrase: &str,
+    path: &str,
+) -> Result<KeyPair, SeedPhraseError> {
+    let mnemonic = Mnemonic::parse_normalized(&normalize_seed_phrase(seed_phrase))
+        .map_err(|err| SeedPhraseError::InvalidPhrase(err.to_string()))?;
+    let seed = mnemonic.to_seed("");
+    let secret = derive_ed25519(&seed, &parse_derivation_path(path)?);
+
+    let secret_key = ed25519_dalek::SecretKey::from_bytes(&secret)
+        .map_err(|err| SeedPhraseError::InvalidKey(err.to_string()))?;
+    let public_key = ed25519_dalek::PublicKey::from(&secret_key);
+    let mut key_bytes = secret.to_vec();
+    key_bytes.extend_from_slice(public_key.as_bytes());
+
+    format!("ed25519:{}", bs58::encode(key_bytes).into_string())
+        .parse()
+        .map_err(|err: near_crypto::ParseKeyError| SeedPhraseError::InvalidKey(err.to_string()))
+}
+
+/// Finds which of the implicit account of the derived key and `candidates` hold the key. Accounts
+/// that do not exist are skipped.
+pub async fn recover_accounts(
+    connection: &Connection,
+    seed_phrase: &str,
+    candidates: &[AccountId],
+) -> Result<SeedPhraseRecovery, AccountError> {
+    let public_key = key_pair_from_seed_phrase(seed_phrase, DEFAULT_DERIVATION_PATH)?.public_key();
+    let mut account_ids = vec![];
+
+    for account_id in implicit_account_id(&public_key).iter().chain(candidates) {
+        if account_ids.contains(account_id) {
+            continue;
+        }
+
+        let account = Account::new(connection.clone(), account_id.clone());
+
+        match account.view_access_key(&public_key).await {
+            Ok(Some(_)) => account_ids.push(account_id.clone()),
+            Ok(None) | Err(AccountError::UnknownAccount(_)) => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(SeedPhraseRecovery {
+        public_key,
+        account_ids,
+    })
+}
+
+/// Trims, lowercases and single-spaces the words, as near-seed-phrase does before parsing.
+fn normalize_seed_phrase(seed_phrase: &str) -> String {
+    seed_phrase
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn parse_derivation_path(path: &str) -> Result<Vec<u32>, SeedPhraseError> {
+    let invalid_path = || SeedPhraseError::InvalidPath(path.to_string());
+    let mut segments = path.split('/');
+
+    if segments.next() != Some("m") {
+        return Err(invalid_path());
+    }
+
+    segments
+        .map(|segment| {
+            let index = segment.strip_suffix('\'').ok_or_else(invalid_path)?;
+            let index = index.parse::<u32>().map_err(|_| invalid_path())?;
+
+            if index < HARDENED_OFFSET {
+                Ok(index + HARDENED_OFFSET)
+            } else {
+                Err(invalid_path())
+            }
+        })
+        .collect()
+}
+
+fn hmac_sha512(key: &[u8], data: &[&[u8]]) -> ([u8; 32], [u8; 32]) {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    for chunk in data {
+        mac.update(chunk);
+    }
+    let output = mac.finalize().into_bytes();
+
+    let mut key = [0; 32];
+    let mut chain_code = [0; 32];
+    key.copy_from_slice(&output[..32]);
+    chain_code.copy_from_slice(&output[32..]);
+    (key, chain_code)
+}
+
+fn derive_ed25519(seed: &[u8], path: &[u32]) -> [u8; 32] {
+    let (mut key, mut chain_code) = hmac_sha512(b"ed25519 seed", &[seed]);
+
+    for index in path {
+        (key, chain_code) = hmac_sha512(&chain_code, &[&[0], &key, &index.to_be_bytes()]);
+    }
+
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED_PHRASE: &str =
+        "shoot island position soft burden budget tooth cruel issue economy destroy above";
+    const PUBLIC_KEY: &str = "ed25519:r4yuiZE45mzeZAENDEF2pWeFBJkW8mQYGx3rU46zCqh";
+
+    #[test]
+    fn derives_known_key() {
+        let key_pair = key_pair_from_seed_phrase(SEED_PHRASE, DEFAULT_DERIVATION_PATH).unwrap();
+
+        assert_eq!(key_pair.public_key().to_string(), PUBLIC_KEY);
+    }
+
+    #[test]
+    fn normalizes_seed_phrase() {
+        let seed_phrase = format!("  {}\n", SEED_PHRASE.to_uppercase().replace(' ', " \t "));
+        let key_pair = key_pair_from_seed_phrase(&seed_phrase, DEFAULT_DERIVATION_PATH).unwrap();
+
+        assert_eq!(key_pair.public_key().to_string(), PUBLIC_KEY);
+    }
+
+    #[test]
+    fn rejects_unhardened_path() {
+        assert!(matches!(
+            key_pair_from_seed_phrase(SEED_PHRASE, "m/44'/397'/0"),
+            Err(SeedPhraseError::InvalidPath(_))
+        ));
+    }
+}
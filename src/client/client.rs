@@ -1,10 +1,12 @@
 use core::fmt;
-use std::str::FromStr;
+use std::{str::FromStr, sync::Arc};
 
 use near_account_id::AccountId;
 use near_crypto::{InMemorySigner, KeyType, SecretKey};
+use near_primitives::types::Balance;
 
 use crate::{
+    account::{Account, AccountCreator, AccountError, LocalAccountCreator, UrlAccountCreator},
     key_stores::{BrowserKeyStore, KeyPair, KeyStore},
     provider::{ConnectionInfo, JsonRpcProvider},
 };
@@ -20,22 +22,74 @@ impl Client {
     pub fn new(config: ClientConfig) -> Self {
         let account_id: AccountId = AccountId::from_str("test.near").unwrap();
         let secret_key = SecretKey::from_random(KeyType::ED25519);
-        let signer = Box::new(InMemorySigner::from_secret_key(
-            account_id.clone(),
-            secret_key.clone(),
-        ));
+        let signer = InMemorySigner::from_secret_key(account_id.clone(), secret_key.clone());
         let provider = JsonRpcProvider::new(ConnectionInfo::new(config.node_url.clone()));
         let mut config = config;
         let mut key_store = BrowserKeyStore::new();
         let key_pair: KeyPair = secret_key.clone();
 
-        key_store.set_key(account_id, config.network_id.as_str(), key_pair);
+        if let Err(err) = key_store.set_key(&account_id, config.network_id.as_str(), key_pair) {
+            log::warn!("Fail to store key for {}: {}", account_id, err);
+        }
 
-        config.signer = Some(signer.clone());
+        config.signer = Some(Box::new(signer.clone()));
         config.key_store = Some(Box::new(key_store));
 
         Self {
-            connection: Connection::new(config.network_id.as_str(), signer, provider),
+            connection: Connection::new(config.network_id.as_str(), Arc::new(signer), provider),
+            config,
+        }
+    }
+
+    /// Creates accounts from `master_account` when configured, signing with its key from
+    /// `key_store`, and falls back to the helper service at `helper_url`.
+    pub fn account_creator(&self) -> Result<Option<Box<dyn AccountCreator>>, AccountError> {
+        let master_account = match self.config.master_account.clone() {
+            Some(master_account) => master_account,
+            None => {
+                return Ok(self.config.helper_url.clone().map(|helper_url| {
+                    Box::new(UrlAccountCreator::new(helper_url)) as Box<dyn AccountCreator>
+                }));
+            }
+        };
+        let initial_balance = match self.config.initial_balance.as_deref() {
+            Some(initial_balance) => initial_balance.parse::<Balance>().map_err(|err| {
+                AccountError::InvalidConfig(format!("Invalid initial balance: {}", err))
+            })?,
+            None => {
+                return Err(AccountError::InvalidConfig(format!(
+                    "Missing initial balance for {}",
+                    master_account
+                )));
+            }
+        };
+        let network_id = self.config.network_id.as_str();
+        let missing_key = || AccountError::MissingKey {
+            account_id: master_account.clone(),
+            network_id: network_id.to_string(),
+        };
+        let secret_key = self
+            .config
+            .key_store
+            .as_ref()
+            .ok_or_else(missing_key)?
+            .get_key(&master_account, network_id)?
+            .ok_or_else(missing_key)?;
+        let signer = InMemorySigner::from_secret_key(master_account.clone(), secret_key);
+        let connection = self.connection.with_signer(Arc::new(signer));
+
+        Ok(Some(Box::new(LocalAccountCreator::new(
+            Account::new(connection, master_account),
+            initial_balance,
+        ))))
+    }
+
+    /// A client that can query the chain but never signs anything.
+    pub fn new_read_only(config: ClientConfig) -> Self {
+        let provider = JsonRpcProvider::new(ConnectionInfo::new(config.node_url.clone()));
+
+        Self {
+            connection: Connection::new_read_only(config.network_id.as_str(), provider),
             config,
         }
     }
@@ -1,33 +1,59 @@
+use std::sync::Arc;
+
 use near_crypto::Signer;
 use serde::{Deserialize, Serialize};
 
 use crate::provider::{ConnectionInfo, JsonRpcProvider, Provider};
 
 use super::ClientConfig;
+
+/// A cheaply cloneable handle; clones share the same provider and signer. A connection without
+/// a signer is read-only.
+#[derive(Clone)]
 pub struct Connection {
     pub network_id: String,
-    pub provider: Provider,
-    pub signer: Box<dyn Signer>,
+    pub provider: Arc<Provider>,
+    pub signer: Option<Arc<dyn Signer>>,
 }
 
 impl Connection {
-    pub fn new(network_id: &str, signer: Box<dyn Signer>, provider: Provider) -> Self {
+    pub fn new(network_id: &str, signer: Arc<dyn Signer>, provider: Provider) -> Self {
+        Self {
+            network_id: network_id.to_string(),
+            signer: Some(signer),
+            provider: Arc::new(provider),
+        }
+    }
+
+    pub fn new_read_only(network_id: &str, provider: Provider) -> Self {
         Self {
             network_id: network_id.to_string(),
-            signer,
-            provider,
+            signer: None,
+            provider: Arc::new(provider),
         }
     }
+
+    /// A connection to the same node that signs with `signer`.
+    pub fn with_signer(&self, signer: Arc<dyn Signer>) -> Self {
+        Self {
+            network_id: self.network_id.clone(),
+            signer: Some(signer),
+            provider: self.provider.clone(),
+        }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.signer.is_none()
+    }
 }
 
 impl From<ClientConfig> for Connection {
     fn from(config: ClientConfig) -> Self {
-        let signer = config.signer.expect("Missing signer");
         let provider = JsonRpcProvider::new(ConnectionInfo::new(config.node_url));
 
         Self {
-            signer,
-            provider,
+            signer: config.signer.map(Arc::from),
+            provider: Arc::new(provider),
             network_id: config.network_id,
         }
     }
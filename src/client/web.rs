@@ -1,9 +1,29 @@
 use wasm_bindgen::JsCast;
 use wasm_bindgen::JsValue;
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{Request, RequestInit, RequestMode, Response};
+use js_sys::Promise;
+use web_sys::{Request, RequestInit, RequestMode, Response, WorkerGlobalScope};
 
+/// Posts `json_str` to `url` and parses the response body as JSON, whatever the status.
 pub async fn fetch_json(url: &str, json_str: &str) -> Result<JsValue, JsValue> {
+    let response = fetch(url, json_str).await?;
+
+    js_sys::JSON::parse(&response.body)
+}
+
+/// Status and raw body of an HTTP response, for callers that need to tell failures apart.
+pub struct FetchResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+impl FetchResponse {
+    pub fn ok(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+pub async fn fetch(url: &str, json_str: &str) -> Result<FetchResponse, JsValue> {
     let mut opts = RequestInit::new();
     opts.method("POST");
     opts.body(Some(&JsValue::from_str(json_str)));
@@ -13,15 +33,24 @@ pub async fn fetch_json(url: &str, json_str: &str) -> Result<JsValue, JsValue> {
 
     request.headers().set("Content-Type", "application/json")?;
 
-    let window = web_sys::window().unwrap();
-    let resp_value = JsFuture::from(window.fetch_with_request(&request)).await?;
+    let resp_value = JsFuture::from(fetch_with_request(&request)?).await?;
+    let resp: Response = resp_value.dyn_into()?;
+    let body = JsFuture::from(resp.text()?).await?.as_string().unwrap_or_default();
 
-    // `resp_value` is a `Response` object.
-    assert!(resp_value.is_instance_of::<Response>());
-    let resp: Response = resp_value.dyn_into().unwrap();
+    Ok(FetchResponse {
+        status: resp.status(),
+        body,
+    })
+}
 
-    // Convert this other `Promise` into a rust `Future`.
-    let json = JsFuture::from(resp.json()?).await?;
+/// Fetches from the window, or from the worker global scope when there is no window.
+fn fetch_with_request(request: &Request) -> Result<Promise, JsValue> {
+    if let Some(window) = web_sys::window() {
+        return Ok(window.fetch_with_request(request));
+    }
 
-    Ok(json)
+    js_sys::global()
+        .dyn_into::<WorkerGlobalScope>()
+        .map(|scope| scope.fetch_with_request(request))
+        .map_err(|_| JsValue::from_str("No window or worker global scope to fetch from"))
 }
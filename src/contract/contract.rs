@@ -1,3 +1,8 @@
+use near_account_id::AccountId;
+
+use super::ContractMethods;
+use crate::account::Account;
+
 pub struct Contract {
     account: Account,
     contract_id: AccountId,
@@ -1,4 +1,11 @@
 mod contract;
+mod staking_pool;
+
+pub use contract::*;
+pub use staking_pool::*;
+
+use near_primitives::types::Balance;
+use serde_json::Value;
 
 pub struct ChangeMethodOptions {
     pub args: Value,
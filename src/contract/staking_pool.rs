@@ -0,0 +1,229 @@
+use near_account_id::AccountId;
+use near_primitives::{
+    serialize::u128_dec_format,
+    types::{Balance, BlockHeightDelta, BlockReference, EpochHeight, Gas},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    account::{Account, AccountError, FunctionCallOptions},
+    provider::types::FinalExecutionOutcome,
+};
+
+/// Number of epochs unstaked tokens stay locked in the standard staking pool contract.
+pub const NUM_EPOCHS_TO_UNLOCK: EpochHeight = 4;
+
+const STAKING_POOL_GAS: Gas = 125_000_000_000_000;
+
+#[derive(Deserialize)]
+struct U128(#[serde(with = "u128_dec_format")] Balance);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UnstakeAvailability {
+    pub available: bool,
+    pub current_epoch_height: EpochHeight,
+    /// The current epoch height when the pool reports the tokens available. Otherwise it is only
+    /// an estimate unless the epoch of the unstake was passed in.
+    pub available_epoch_height: EpochHeight,
+    /// Estimated from the current epoch's start height and the protocol's epoch length.
+    pub remaining_blocks: BlockHeightDelta,
+}
+
+impl UnstakeAvailability {
+    fn available(current_epoch_height: EpochHeight) -> Self {
+        Self {
+            available: true,
+            current_epoch_height,
+            available_epoch_height: current_epoch_height,
+            remaining_blocks: 0,
+        }
+    }
+
+    /// `blocks_into_epoch` is how far the latest block is past the current epoch's start.
+    fn locked(
+        current_epoch_height: EpochHeight,
+        unstaked_at_epoch_height: Option<EpochHeight>,
+        epoch_length: BlockHeightDelta,
+        blocks_into_epoch: BlockHeightDelta,
+    ) -> Self {
+        let available_epoch_height =
+            unstaked_at_epoch_height.unwrap_or(current_epoch_height) + NUM_EPOCHS_TO_UNLOCK;
+        let remaining_epochs = available_epoch_height.saturating_sub(current_epoch_height);
+
+        Self {
+            available: false,
+            current_epoch_height,
+            available_epoch_height,
+            remaining_blocks: (remaining_epochs * epoch_length).saturating_sub(blocks_into_epoch),
+        }
+    }
+}
+
+/// Client for the standard staking pool contract, acting on behalf of `account`.
+pub struct StakingPool {
+    account: Account,
+    contract_id: AccountId,
+}
+
+impl StakingPool {
+    pub fn new(account: Account, contract_id: AccountId) -> Self {
+        Self {
+            account,
+            contract_id,
+        }
+    }
+
+    pub async fn deposit_and_stake(
+        &self,
+        amount: Balance,
+    ) -> Result<FinalExecutionOutcome, AccountError> {
+        self.call("deposit_and_stake", json!({}), amount).await
+    }
+
+    pub async fn unstake(
+        &self,
+        amount: Balance,
+    ) -> Result<FinalExecutionOutcome, AccountError> {
+        self.call("unstake", json!({ "amount": amount.to_string() }), 0).await
+    }
+
+    pub async fn unstake_all(&self) -> Result<FinalExecutionOutcome, AccountError> {
+        self.call("unstake_all", json!({}), 0).await
+    }
+
+    pub async fn withdraw_all(&self) -> Result<FinalExecutionOutcome, AccountError> {
+        self.call("withdraw_all", json!({}), 0).await
+    }
+
+    pub async fn get_account_staked_balance(&self) -> Result<Balance, AccountError> {
+        self.view_balance("get_account_staked_balance").await
+    }
+
+    pub async fn get_account_unstaked_balance(&self) -> Result<Balance, AccountError> {
+        self.view_balance("get_account_unstaked_balance").await
+    }
+
+    pub async fn is_account_unstaked_balance_available(&self) -> Result<bool, AccountError> {
+        let args = json!({ "account_id": self.account.account_id() });
+
+        self.account
+            .view_function(
+                self.contract_id.clone(),
+                "is_account_unstaked_balance_available".to_string(),
+                args,
+            )
+            .await
+    }
+
+    /// Reports when unstaked tokens can be withdrawn. The pool does not expose the epoch of the
+    /// last unstake, so pass it if known; otherwise the current epoch is assumed and the result
+    /// is an estimate giving the latest possible answer.
+    pub async fn get_unstake_availability(
+        &self,
+        unstaked_at_epoch_height: Option<EpochHeight>,
+    ) -> Result<UnstakeAvailability, AccountError> {
+        let provider = &self.account.connection().provider;
+        let available = self.is_account_unstaked_balance_available().await?;
+        let validators = provider.validators(None).await?;
+
+        if available {
+            return Ok(UnstakeAvailability::available(validators.epoch_height));
+        }
+
+        let protocol_config = provider
+            .experimental_protocol_config(BlockReference::latest())
+            .await?;
+        let block = provider.block(BlockReference::latest()).await?;
+        let blocks_into_epoch = block
+            .header
+            .height
+            .saturating_sub(validators.epoch_start_height);
+
+        Ok(UnstakeAvailability::locked(
+            validators.epoch_height,
+            unstaked_at_epoch_height,
+            protocol_config.epoch_length,
+            blocks_into_epoch,
+        ))
+    }
+
+    async fn call(
+        &self,
+        method_name: &str,
+        args: serde_json::Value,
+        deposit: Balance,
+    ) -> Result<FinalExecutionOutcome, AccountError> {
+        self.account
+            .function_call(FunctionCallOptions {
+                contract_id: self.contract_id.clone(),
+                method_name: method_name.to_string(),
+                args,
+                gas: Some(STAKING_POOL_GAS),
+                attached_deposit: Some(deposit),
+                wallet_meta: None,
+                wallet_callback_url: None,
+            })
+            .await
+    }
+
+    async fn view_balance(&self, method_name: &str) -> Result<Balance, AccountError> {
+        let args = json!({ "account_id": self.account.account_id() });
+        let balance: U128 = self
+            .account
+            .view_function(self.contract_id.clone(), method_name.to_string(), args)
+            .await?;
+
+        Ok(balance.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPOCH_LENGTH: BlockHeightDelta = 43_200;
+
+    #[test]
+    fn available_tokens_report_the_current_epoch() {
+        let availability = UnstakeAvailability::available(1_500);
+
+        assert!(availability.available);
+        assert_eq!(availability.current_epoch_height, 1_500);
+        assert_eq!(availability.available_epoch_height, 1_500);
+        assert_eq!(availability.remaining_blocks, 0);
+    }
+
+    #[test]
+    fn unknown_unstake_epoch_assumes_the_current_one() {
+        let availability = UnstakeAvailability::locked(1_500, None, EPOCH_LENGTH, 1_000);
+
+        assert!(!availability.available);
+        assert_eq!(availability.available_epoch_height, 1_500 + NUM_EPOCHS_TO_UNLOCK);
+        assert_eq!(
+            availability.remaining_blocks,
+            NUM_EPOCHS_TO_UNLOCK * EPOCH_LENGTH - 1_000
+        );
+    }
+
+    #[test]
+    fn counts_epochs_from_the_unstake() {
+        let availability = UnstakeAvailability::locked(1_500, Some(1_498), EPOCH_LENGTH, 40_000);
+
+        assert_eq!(availability.current_epoch_height, 1_500);
+        assert_eq!(availability.available_epoch_height, 1_502);
+        assert_eq!(availability.remaining_blocks, 2 * EPOCH_LENGTH - 40_000);
+    }
+
+    #[test]
+    fn remaining_blocks_never_underflow() {
+        // The pool only releases the tokens once it sees the new epoch, which can lag behind.
+        let availability = UnstakeAvailability::locked(1_500, Some(1_490), EPOCH_LENGTH, 1_000);
+        assert_eq!(availability.available_epoch_height, 1_494);
+        assert_eq!(availability.remaining_blocks, 0);
+
+        let availability = UnstakeAvailability::locked(1_500, Some(1_496), EPOCH_LENGTH, 50_000);
+        assert_eq!(availability.available_epoch_height, 1_500);
+        assert_eq!(availability.remaining_blocks, 0);
+    }
+}
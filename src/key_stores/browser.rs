@@ -1,10 +1,10 @@
 use near_account_id::AccountId;
+use wasm_bindgen::JsValue;
 
-use super::{KeyPair, KeyStore};
+use super::{decode_key_pairs, encode_key_pairs, KeyPair, KeyStore, KeyStoreError};
 use core::fmt;
-use core::str::FromStr;
 
-const LOCAL_STORAGE_KEY_PREFIX: &str = "near-api-rs_keystore";
+pub(crate) const LOCAL_STORAGE_KEY_PREFIX: &str = "near-api-rs_keystore";
 
 #[derive(Clone)]
 pub struct BrowserKeyStore {
@@ -39,6 +39,49 @@ impl BrowserKeyStore {
             .map(|s| s.to_string())
             .collect::<Vec<String>>()
     }
+
+    /// Storage keys of all of this store's entries, rotation keys included.
+    fn _entry_keys(&self) -> Result<Vec<String>, KeyStoreError> {
+        let length = self.storage.length().map_err(storage_error)?;
+        let prefix = format!("{}:", self.prefix);
+        let mut keys = vec![];
+
+        for i in 0..length {
+            if let Some(key) = self.storage.key(i).map_err(storage_error)? {
+                if key.starts_with(&prefix) {
+                    keys.push(key);
+                }
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Storage keys of this store's entries as `[prefix, account_id, network_id]`.
+    fn _entries(&self) -> Result<Vec<Vec<String>>, KeyStoreError> {
+        Ok(self
+            ._entry_keys()?
+            .iter()
+            .map(|key| Self::_extract_storage_key(key))
+            .filter(|key| key.len() == 3)
+            .collect())
+    }
+
+    fn _get_entry(&self, key: &str) -> Result<Vec<KeyPair>, KeyStoreError> {
+        match self.storage.get_item(key).map_err(storage_error)? {
+            Some(value) => decode_key_pairs(key, &value),
+            None => Ok(vec![]),
+        }
+    }
+
+    fn _set_entry(&mut self, key: &str, key_pairs: Vec<KeyPair>) -> Result<(), KeyStoreError> {
+        if key_pairs.is_empty() {
+            self.storage.remove_item(key).map_err(storage_error)
+        } else {
+            self.storage
+                .set_item(key, &encode_key_pairs(&key_pairs))
+                .map_err(storage_error)
+        }
+    }
 }
 
 impl fmt::Display for BrowserKeyStore {
@@ -48,59 +91,53 @@ impl fmt::Display for BrowserKeyStore {
 }
 
 impl KeyStore for BrowserKeyStore {
-    fn set_key(&mut self, account_id: AccountId, network_id: &str, key_pair: KeyPair) {
+    fn get_keys(
+        &self,
+        account_id: &AccountId,
+        network_id: &str,
+    ) -> Result<Vec<KeyPair>, KeyStoreError> {
         let key = Self::_get_storage_key(&self.prefix, account_id.as_str(), network_id);
-        let value = key_pair.to_string();
 
-        self.storage
-            .set_item(&key, &value)
-            .expect("Fail to delete key from storage");
+        self._get_entry(&key)
     }
 
-    fn get_key(&self, account_id: AccountId, network_id: &str) -> KeyPair {
+    fn set_keys(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+        key_pairs: Vec<KeyPair>,
+    ) -> Result<(), KeyStoreError> {
         let key = Self::_get_storage_key(&self.prefix, account_id.as_str(), network_id);
-        let value = self.storage.get_item(&key).expect("Invalid key").unwrap();
-        let key_pair = KeyPair::from_str(value.as_str()).expect("Invalid key data");
 
-        key_pair
+        self._set_entry(&key, key_pairs)
     }
-    fn remove_key(&mut self, account_id: AccountId, network_id: &str) {
-        let key = Self::_get_storage_key(&self.prefix, account_id.as_str(), network_id);
 
-        self.storage
-            .delete(&key)
-            .expect("Fail to delete key from storage");
-    }
-    fn clear(&mut self) {
-        self.storage.clear().expect("Fail to clear storage");
-    }
-    fn get_networks(&self) -> Vec<String> {
-        let l = self.storage.length().unwrap();
-        let mut keys = vec![];
-        for i in 0..l {
-            let key = self.storage.key(i);
-            if key.is_ok() {
-                let key_str = Self::_extract_storage_key(&key.unwrap().unwrap());
-                if key_str.len() == 3 {
-                    keys.push(key_str[2].clone());
-                }
-            }
+    /// Removes this store's entries, leaving the rest of local storage alone.
+    fn clear(&mut self) -> Result<(), KeyStoreError> {
+        for key in self._entry_keys()? {
+            self.storage.remove_item(&key).map_err(storage_error)?;
         }
-        keys
+        Ok(())
     }
 
-    fn get_accounts(&self, network_id: &str) -> Vec<String> {
-        let l = self.storage.length().unwrap();
-        let mut keys = vec![];
-        for i in 0..l {
-            let key = self.storage.key(i);
-            if key.is_ok() {
-                let key_str = Self::_extract_storage_key(&key.unwrap().unwrap());
-                if key_str.len() == 3 && key_str[2] == network_id {
-                    keys.push(key_str[1].clone());
-                }
-            }
-        }
-        keys
+    fn get_networks(&self) -> Result<Vec<String>, KeyStoreError> {
+        Ok(self
+            ._entries()?
+            .into_iter()
+            .map(|key| key[2].clone())
+            .collect())
     }
+
+    fn get_accounts(&self, network_id: &str) -> Result<Vec<String>, KeyStoreError> {
+        Ok(self
+            ._entries()?
+            .into_iter()
+            .filter(|key| key[2] == network_id)
+            .map(|key| key[1].clone())
+            .collect())
+    }
+}
+
+pub(crate) fn storage_error(err: JsValue) -> KeyStoreError {
+    KeyStoreError::Storage(format!("{:?}", err))
 }
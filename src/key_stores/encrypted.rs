@@ -0,0 +1,532 @@
+use core::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use hashbrown::HashMap;
+use near_account_id::AccountId;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use super::{
+    browser::storage_error, decode_key_pairs, encode_key_pairs, KeyPair, KeyStore, KeyStoreError,
+};
+
+const ENCRYPTED_KEY_STORE_PREFIX: &str = "near-api-rs_encrypted_keystore";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+
+type SecretBytes = Zeroizing<[u8; KEY_LEN]>;
+
+/// Raw string storage that `EncryptedKeyStore` writes its ciphertext to.
+pub trait KeyValueStorage: Clone + 'static {
+    fn get_item(&self, key: &str) -> Result<Option<String>, KeyStoreError>;
+    fn set_item(&mut self, key: &str, value: &str) -> Result<(), KeyStoreError>;
+    fn remove_item(&mut self, key: &str) -> Result<(), KeyStoreError>;
+    fn keys(&self) -> Result<Vec<String>, KeyStoreError>;
+}
+
+impl KeyValueStorage for web_sys::Storage {
+    fn get_item(&self, key: &str) -> Result<Option<String>, KeyStoreError> {
+        web_sys::Storage::get_item(self, key).map_err(storage_error)
+    }
+
+    fn set_item(&mut self, key: &str, value: &str) -> Result<(), KeyStoreError> {
+        web_sys::Storage::set_item(self, key, value).map_err(storage_error)
+    }
+
+    fn remove_item(&mut self, key: &str) -> Result<(), KeyStoreError> {
+        web_sys::Storage::remove_item(self, key).map_err(storage_error)
+    }
+
+    fn keys(&self) -> Result<Vec<String>, KeyStoreError> {
+        let length = self.length().map_err(storage_error)?;
+        let mut keys = vec![];
+
+        for i in 0..length {
+            keys.extend(self.key(i).map_err(storage_error)?);
+        }
+        Ok(keys)
+    }
+}
+
+impl KeyValueStorage for HashMap<String, String> {
+    fn get_item(&self, key: &str) -> Result<Option<String>, KeyStoreError> {
+        Ok(self.get(key).cloned())
+    }
+
+    fn set_item(&mut self, key: &str, value: &str) -> Result<(), KeyStoreError> {
+        self.insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    fn remove_item(&mut self, key: &str) -> Result<(), KeyStoreError> {
+        self.remove(key);
+        Ok(())
+    }
+
+    fn keys(&self) -> Result<Vec<String>, KeyStoreError> {
+        Ok(HashMap::keys(self).cloned().collect())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EncryptedKeyStoreError {
+    #[error("Wrong passphrase")]
+    WrongPassphrase,
+    #[error("Key store has no passphrase yet")]
+    NotInitialized,
+    #[error("Key store already has a passphrase")]
+    AlreadyInitialized,
+    #[error("Corrupted key store entry {0}")]
+    Corrupted(String),
+    #[error("Encryption failed: {0}")]
+    Crypto(String),
+}
+
+/// Stored next to the entries: how to derive the passphrase key, and the data key that encrypts
+/// the entries, itself encrypted with the passphrase key.
+#[derive(Serialize, Deserialize)]
+struct EncryptionMeta {
+    salt: String,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    data_key: String,
+}
+
+/// A `KeyStore` that keeps keys encrypted with ChaCha20-Poly1305 under a random data key, which
+/// is stored encrypted under a key derived from a passphrase with Argon2id. A new store gets its
+/// passphrase from `initialize`, an existing one starts locked; reading or writing keys while
+/// locked fails with `KeyStoreError::Locked`. Clones share the
+/// unlocked data key, so locking one locks them all.
+#[derive(Clone)]
+pub struct EncryptedKeyStore<S: KeyValueStorage> {
+    prefix: String,
+    storage: S,
+    data_key: Arc<Mutex<Option<SecretBytes>>>,
+}
+
+impl<S: KeyValueStorage> EncryptedKeyStore<S> {
+    pub fn new(storage: S) -> Self {
+        Self::new_with_prefix(storage, ENCRYPTED_KEY_STORE_PREFIX)
+    }
+
+    pub fn new_with_prefix(storage: S, prefix: &str) -> Self {
+        Self {
+            prefix: String::from(prefix),
+            storage,
+            data_key: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn is_initialized(&self) -> Result<bool, KeyStoreError> {
+        Ok(self.storage.get_item(&self._get_meta_key())?.is_some())
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self._data_key().is_none()
+    }
+
+    /// Sets the passphrase of a new store and unlocks it.
+    pub fn initialize(&mut self, passphrase: &str) -> Result<(), KeyStoreError> {
+        if self.is_initialized()? {
+            return Err(EncryptedKeyStoreError::AlreadyInitialized.into());
+        }
+
+        let data_key = Self::_random_key()?;
+        let meta = self._wrap_data_key(&data_key, passphrase)?;
+
+        self._set_meta(&meta)?;
+        *self._data_key() = Some(data_key);
+        Ok(())
+    }
+
+    pub fn unlock(&mut self, passphrase: &str) -> Result<(), KeyStoreError> {
+        let meta = self._get_meta()?.ok_or(EncryptedKeyStoreError::NotInitialized)?;
+        let data_key = self._unwrap_data_key(&meta, passphrase)?;
+
+        *self._data_key() = Some(data_key);
+        Ok(())
+    }
+
+    /// Forgets the data key, in every clone of this store.
+    pub fn lock(&self) {
+        *self._data_key() = None;
+    }
+
+    /// Re-encrypts the data key under `new_passphrase`. Entries are left as they are, so the
+    /// change is a single write.
+    pub fn change_passphrase(
+        &mut self,
+        passphrase: &str,
+        new_passphrase: &str,
+    ) -> Result<(), KeyStoreError> {
+        let meta = self._get_meta()?.ok_or(EncryptedKeyStoreError::NotInitialized)?;
+        let data_key = self._unwrap_data_key(&meta, passphrase)?;
+        let meta = self._wrap_data_key(&data_key, new_passphrase)?;
+
+        self._set_meta(&meta)?;
+        *self._data_key() = Some(data_key);
+        Ok(())
+    }
+
+    fn _get_meta_key(&self) -> String {
+        format!("{}-meta", self.prefix)
+    }
+
+    fn _get_storage_key(prefix: &str, account_id: &str, network_id: &str) -> String {
+        format!("{}:{}:{}", prefix, account_id, network_id)
+    }
+
+    fn _extract_storage_key(storage_key: &str) -> Vec<String> {
+        storage_key
+            .split(':')
+            .map(|s| s.to_string())
+            .collect::<Vec<String>>()
+    }
+
+    /// Storage keys of this store's entries as `[prefix, account_id, network_id]`.
+    fn _entries(&self) -> Result<Vec<Vec<String>>, KeyStoreError> {
+        Ok(self
+            .storage
+            .keys()?
+            .iter()
+            .map(|key| Self::_extract_storage_key(key))
+            .filter(|key| key.len() == 3 && key[0] == self.prefix)
+            .collect())
+    }
+
+    /// Storage keys of all of this store's entries, rotation keys included.
+    fn _entry_keys(&self) -> Result<Vec<String>, KeyStoreError> {
+        let prefix = format!("{}:", self.prefix);
+
+        Ok(self
+            .storage
+            .keys()?
+            .into_iter()
+            .filter(|key| key.starts_with(&prefix))
+            .collect())
+    }
+
+    fn _get_entry(&self, key: &str) -> Result<Vec<KeyPair>, KeyStoreError> {
+        let value = match self.storage.get_item(key)? {
+            Some(value) => value,
+            None => return Ok(vec![]),
+        };
+        let plaintext = Self::_decrypt(&self._unlocked_key()?, key, &value)?;
+
+        decode_key_pairs(key, &String::from_utf8_lossy(&plaintext))
+    }
+
+    fn _set_entry(&mut self, key: &str, key_pairs: Vec<KeyPair>) -> Result<(), KeyStoreError> {
+        if key_pairs.is_empty() {
+            return self.storage.remove_item(key);
+        }
+
+        let plaintext = encode_key_pairs(&key_pairs);
+        let value = Self::_encrypt(&self._unlocked_key()?, key, plaintext.as_bytes())?;
+
+        self.storage.set_item(key, &value)
+    }
+
+    fn _get_meta(&self) -> Result<Option<EncryptionMeta>, KeyStoreError> {
+        let meta_key = self._get_meta_key();
+
+        match self.storage.get_item(&meta_key)? {
+            Some(value) => serde_json::from_str(&value)
+                .map(Some)
+                .map_err(|_| EncryptedKeyStoreError::Corrupted(meta_key).into()),
+            None => Ok(None),
+        }
+    }
+
+    fn _set_meta(&mut self, meta: &EncryptionMeta) -> Result<(), KeyStoreError> {
+        let value = serde_json::to_string(meta).expect("Fail to serialize key store meta");
+
+        self.storage.set_item(&self._get_meta_key(), &value)
+    }
+
+    /// Encrypts `data_key` under a key derived from `passphrase` with a fresh salt.
+    fn _wrap_data_key(
+        &self,
+        data_key: &SecretBytes,
+        passphrase: &str,
+    ) -> Result<EncryptionMeta, EncryptedKeyStoreError> {
+        let mut salt = [0; SALT_LEN];
+        getrandom::getrandom(&mut salt)
+            .map_err(|err| EncryptedKeyStoreError::Crypto(err.to_string()))?;
+        let mut meta = EncryptionMeta {
+            salt: base64::encode(salt),
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+            data_key: String::new(),
+        };
+        let key = Self::_derive_key(&meta, passphrase)?;
+        meta.data_key = Self::_encrypt(&key, &self._get_meta_key(), data_key.as_slice())?;
+
+        Ok(meta)
+    }
+
+    fn _unwrap_data_key(
+        &self,
+        meta: &EncryptionMeta,
+        passphrase: &str,
+    ) -> Result<SecretBytes, EncryptedKeyStoreError> {
+        let key = Self::_derive_key(meta, passphrase)?;
+        let plaintext = Zeroizing::new(
+            Self::_decrypt(&key, &self._get_meta_key(), &meta.data_key)
+                .map_err(|_| EncryptedKeyStoreError::WrongPassphrase)?,
+        );
+
+        if plaintext.len() != KEY_LEN {
+            return Err(EncryptedKeyStoreError::Corrupted(self._get_meta_key()));
+        }
+
+        let mut data_key = Zeroizing::new([0; KEY_LEN]);
+        data_key.copy_from_slice(&plaintext);
+        Ok(data_key)
+    }
+
+    fn _random_key() -> Result<SecretBytes, EncryptedKeyStoreError> {
+        let mut key = Zeroizing::new([0; KEY_LEN]);
+        getrandom::getrandom(key.as_mut_slice())
+            .map_err(|err| EncryptedKeyStoreError::Crypto(err.to_string()))?;
+
+        Ok(key)
+    }
+
+    fn _derive_key(
+        meta: &EncryptionMeta,
+        passphrase: &str,
+    ) -> Result<SecretBytes, EncryptedKeyStoreError> {
+        let salt = base64::decode(&meta.salt)
+            .map_err(|_| EncryptedKeyStoreError::Corrupted("salt".to_string()))?;
+        let params = Params::new(meta.m_cost, meta.t_cost, meta.p_cost, Some(KEY_LEN))
+            .map_err(|err| EncryptedKeyStoreError::Crypto(err.to_string()))?;
+        let mut key = Zeroizing::new([0; KEY_LEN]);
+
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, key.as_mut_slice())
+            .map_err(|err| EncryptedKeyStoreError::Crypto(err.to_string()))?;
+
+        Ok(key)
+    }
+
+    /// Encrypts to base64 of `nonce || ciphertext`, binding the ciphertext to `storage_key` so
+    /// that entries can not be swapped between accounts.
+    fn _encrypt(
+        key: &[u8; KEY_LEN],
+        storage_key: &str,
+        plaintext: &[u8],
+    ) -> Result<String, EncryptedKeyStoreError> {
+        let mut nonce = [0; NONCE_LEN];
+        getrandom::getrandom(&mut nonce)
+            .map_err(|err| EncryptedKeyStoreError::Crypto(err.to_string()))?;
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+        let payload = Payload {
+            msg: plaintext,
+            aad: storage_key.as_bytes(),
+        };
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), payload)
+            .map_err(|err| EncryptedKeyStoreError::Crypto(err.to_string()))?;
+
+        let mut value = nonce.to_vec();
+        value.extend(ciphertext);
+        Ok(base64::encode(value))
+    }
+
+    fn _decrypt(
+        key: &[u8; KEY_LEN],
+        storage_key: &str,
+        value: &str,
+    ) -> Result<Vec<u8>, EncryptedKeyStoreError> {
+        let corrupted = || EncryptedKeyStoreError::Corrupted(storage_key.to_string());
+        let value = base64::decode(value).map_err(|_| corrupted())?;
+
+        if value.len() <= NONCE_LEN {
+            return Err(corrupted());
+        }
+
+        let (nonce, ciphertext) = value.split_at(NONCE_LEN);
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+        let payload = Payload {
+            msg: ciphertext,
+            aad: storage_key.as_bytes(),
+        };
+
+        cipher
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| corrupted())
+    }
+
+    fn _data_key(&self) -> MutexGuard<'_, Option<SecretBytes>> {
+        // A key is either set or not, so a panicking holder can not leave it half written.
+        self.data_key.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// A copy of the data key that is wiped once dropped.
+    fn _unlocked_key(&self) -> Result<SecretBytes, KeyStoreError> {
+        self._data_key().clone().ok_or(KeyStoreError::Locked)
+    }
+}
+
+impl<S: KeyValueStorage> fmt::Display for EncryptedKeyStore<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "EncryptedKeyStore")
+    }
+}
+
+impl<S: KeyValueStorage> KeyStore for EncryptedKeyStore<S> {
+    fn get_keys(
+        &self,
+        account_id: &AccountId,
+        network_id: &str,
+    ) -> Result<Vec<KeyPair>, KeyStoreError> {
+        let key = Self::_get_storage_key(&self.prefix, account_id.as_str(), network_id);
+
+        self._get_entry(&key)
+    }
+
+    fn set_keys(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+        key_pairs: Vec<KeyPair>,
+    ) -> Result<(), KeyStoreError> {
+        let key = Self::_get_storage_key(&self.prefix, account_id.as_str(), network_id);
+
+        self._set_entry(&key, key_pairs)
+    }
+
+    /// Removes every entry but keeps the passphrase.
+    fn clear(&mut self) -> Result<(), KeyStoreError> {
+        for key in self._entry_keys()? {
+            self.storage.remove_item(&key)?;
+        }
+        Ok(())
+    }
+
+    fn get_networks(&self) -> Result<Vec<String>, KeyStoreError> {
+        Ok(self._entries()?.into_iter().map(|key| key[2].clone()).collect())
+    }
+
+    fn get_accounts(&self, network_id: &str) -> Result<Vec<String>, KeyStoreError> {
+        Ok(self
+            ._entries()?
+            .into_iter()
+            .filter(|key| key[2] == network_id)
+            .map(|key| key[1].clone())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::{KeyType, SecretKey};
+
+    use super::*;
+
+    fn account_id() -> AccountId {
+        "alice.near".parse().unwrap()
+    }
+
+    fn unlocked_store(passphrase: &str) -> EncryptedKeyStore<HashMap<String, String>> {
+        let mut key_store = EncryptedKeyStore::new(HashMap::new());
+        key_store.initialize(passphrase).unwrap();
+        key_store
+    }
+
+    #[test]
+    fn passphrase_is_set_explicitly() {
+        let mut key_store = EncryptedKeyStore::new(HashMap::new());
+
+        assert!(matches!(
+            key_store.unlock("passphrase"),
+            Err(KeyStoreError::Encryption(EncryptedKeyStoreError::NotInitialized))
+        ));
+        assert!(!key_store.is_initialized().unwrap());
+        assert!(key_store.is_locked());
+
+        key_store.initialize("passphrase").unwrap();
+        assert!(!key_store.is_locked());
+        assert!(matches!(
+            key_store.initialize("other passphrase"),
+            Err(KeyStoreError::Encryption(EncryptedKeyStoreError::AlreadyInitialized))
+        ));
+
+        key_store.lock();
+        key_store.unlock("passphrase").unwrap();
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut key_store = unlocked_store("passphrase");
+        let key_pair = SecretKey::from_random(KeyType::ED25519);
+
+        key_store.set_key(&account_id(), "testnet", key_pair.clone()).unwrap();
+
+        assert_eq!(key_store.get_key(&account_id(), "testnet").unwrap(), Some(key_pair.clone()));
+        assert!(key_store
+            .storage
+            .values()
+            .all(|value| !value.contains(&key_pair.to_string())));
+    }
+
+    #[test]
+    fn wrong_passphrase() {
+        let mut key_store = unlocked_store("passphrase");
+        key_store
+            .set_key(&account_id(), "testnet", SecretKey::from_random(KeyType::ED25519))
+            .unwrap();
+        let mut reopened = EncryptedKeyStore::new(key_store.storage.clone());
+
+        assert!(matches!(
+            reopened.unlock("wrong passphrase"),
+            Err(KeyStoreError::Encryption(EncryptedKeyStoreError::WrongPassphrase))
+        ));
+        assert!(reopened.is_locked());
+        assert!(matches!(
+            reopened.get_keys(&account_id(), "testnet"),
+            Err(KeyStoreError::Locked)
+        ));
+    }
+
+    #[test]
+    fn lock_locks_clones() {
+        let key_store = unlocked_store("passphrase");
+        let clone = key_store.clone();
+
+        key_store.lock();
+
+        assert!(clone.is_locked());
+    }
+
+    #[test]
+    fn change_passphrase() {
+        let mut key_store = unlocked_store("passphrase");
+        let key_pair = SecretKey::from_random(KeyType::ED25519);
+        key_store.set_key(&account_id(), "testnet", key_pair.clone()).unwrap();
+        let entries = key_store.storage.clone();
+
+        key_store.change_passphrase("passphrase", "new passphrase").unwrap();
+
+        // Only the meta entry changes.
+        for (key, value) in &entries {
+            if *key != key_store._get_meta_key() {
+                assert_eq!(key_store.storage.get(key), Some(value));
+            }
+        }
+
+        let mut reopened = EncryptedKeyStore::new(key_store.storage.clone());
+        assert!(reopened.unlock("passphrase").is_err());
+        reopened.unlock("new passphrase").unwrap();
+        assert_eq!(reopened.get_key(&account_id(), "testnet").unwrap(), Some(key_pair));
+    }
+}
@@ -0,0 +1,344 @@
+use core::fmt;
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use near_account_id::AccountId;
+use near_crypto::PublicKey;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    decode_key_pairs, encode_key_pairs, KeyPair, KeyStore, KeyStoreError, ROTATION_KEYS_SUFFIX,
+};
+
+const CREDENTIALS_DIR: &str = ".near-credentials";
+
+/// The contents of a near-cli credentials file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KeyFile {
+    pub account_id: AccountId,
+    pub public_key: PublicKey,
+    #[serde(alias = "secret_key")]
+    pub private_key: KeyPair,
+}
+
+/// Stores keys as `<key_dir>/<network_id>/<account_id>.json`, the layout near-cli uses under
+/// `~/.near-credentials`. Further keys of an account go to `<key_dir>/<network_id>/<account_id>/`
+/// and rotation keys to `<key_dir>/<network_id>/<account_id>.rotation`. Further keys are named
+/// after their public key, so `get_keys` returns them in that order rather than the order they
+/// were set in.
+#[derive(Clone)]
+pub struct UnencryptedFileSystemKeyStore {
+    key_dir: PathBuf,
+}
+
+impl UnencryptedFileSystemKeyStore {
+    /// Uses `~/.near-credentials`.
+    pub fn new() -> Result<Self, KeyStoreError> {
+        let home = std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .ok_or_else(|| KeyStoreError::Storage("Fail to get home directory".to_string()))?;
+
+        Ok(Self::new_with_dir(PathBuf::from(home).join(CREDENTIALS_DIR)))
+    }
+
+    pub fn new_with_dir(key_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            key_dir: key_dir.into(),
+        }
+    }
+
+    fn _get_key_file_path(&self, account_id: &str, network_id: &str) -> PathBuf {
+        self.key_dir
+            .join(network_id)
+            .join(format!("{}.json", account_id))
+    }
+
+    fn _get_rotation_file_path(&self, account_id: &str, network_id: &str) -> PathBuf {
+        self.key_dir
+            .join(network_id)
+            .join(format!("{}.{}", account_id, ROTATION_KEYS_SUFFIX))
+    }
+
+    fn _get_extra_key_dir(&self, account_id: &str, network_id: &str) -> PathBuf {
+        self.key_dir.join(network_id).join(account_id)
+    }
+
+    /// Names of the entries in `dir`, with whether each is a directory. A missing directory is
+    /// empty.
+    fn _list_dir(&self, dir: &Path) -> Vec<(String, bool)> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return vec![],
+        };
+
+        entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let is_dir = entry.file_type().ok()?.is_dir();
+
+                Some((entry.file_name().into_string().ok()?, is_dir))
+            })
+            .collect()
+    }
+
+    fn _read_key_file(path: &Path) -> Result<Option<KeyFile>, KeyStoreError> {
+        let value = match fs::read_to_string(path) {
+            Ok(value) => value,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(io_error(err)),
+        };
+
+        serde_json::from_str(&value)
+            .map(Some)
+            .map_err(|_| KeyStoreError::InvalidKeyData(path.display().to_string()))
+    }
+
+    fn _write_key_file(
+        path: &Path,
+        account_id: &AccountId,
+        key_pair: KeyPair,
+    ) -> Result<(), KeyStoreError> {
+        let key_file = KeyFile {
+            account_id: account_id.clone(),
+            public_key: key_pair.public_key(),
+            private_key: key_pair,
+        };
+        let value = serde_json::to_string(&key_file).expect("Fail to serialize key file");
+
+        Self::_write_private_file(path, &value)
+    }
+
+    /// Writes `value` to a temporary file next to `path` and renames it over `path`, so that an
+    /// interruption never leaves a truncated key file. Secret keys should only be readable by
+    /// their owner, as near-cli does.
+    fn _write_private_file(path: &Path, value: &str) -> Result<(), KeyStoreError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(io_error)?;
+        }
+
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        let temp_path = PathBuf::from(temp_path);
+        // The mode only applies to new files, so never reuse a leftover temporary file.
+        if let Err(err) = fs::remove_file(&temp_path) {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                return Err(io_error(err));
+            }
+        }
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+
+            options.mode(0o600);
+        }
+        let mut file = options.open(&temp_path).map_err(io_error)?;
+        file.write_all(value.as_bytes())
+            .and_then(|_| file.sync_all())
+            .map_err(io_error)?;
+
+        fs::rename(&temp_path, path).map_err(io_error)
+    }
+}
+
+impl fmt::Display for UnencryptedFileSystemKeyStore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "UnencryptedFileSystemKeyStore({})", self.key_dir.display())
+    }
+}
+
+impl KeyStore for UnencryptedFileSystemKeyStore {
+    fn get_keys(
+        &self,
+        account_id: &AccountId,
+        network_id: &str,
+    ) -> Result<Vec<KeyPair>, KeyStoreError> {
+        let extra_key_dir = self._get_extra_key_dir(account_id.as_str(), network_id);
+        let mut paths = vec![self._get_key_file_path(account_id.as_str(), network_id)];
+        let mut extra_paths = self
+            ._list_dir(&extra_key_dir)
+            .into_iter()
+            .filter(|(name, is_dir)| !is_dir && name.ends_with(".json"))
+            .map(|(name, _)| extra_key_dir.join(name))
+            .collect::<Vec<_>>();
+        extra_paths.sort();
+        paths.extend(extra_paths);
+
+        let mut key_pairs: Vec<KeyPair> = vec![];
+        for path in paths {
+            if let Some(key_file) = Self::_read_key_file(&path)? {
+                let public_key = key_file.private_key.public_key();
+
+                if key_pairs.iter().all(|key_pair| key_pair.public_key() != public_key) {
+                    key_pairs.push(key_file.private_key);
+                }
+            }
+        }
+        Ok(key_pairs)
+    }
+
+    /// The first key goes to the near-cli key file, the others next to it in a directory named
+    /// after the account. New files are written before stale ones are removed, so an interruption
+    /// never loses a key.
+    fn set_keys(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+        key_pairs: Vec<KeyPair>,
+    ) -> Result<(), KeyStoreError> {
+        let path = self._get_key_file_path(account_id.as_str(), network_id);
+        let extra_key_dir = self._get_extra_key_dir(account_id.as_str(), network_id);
+        let mut key_pairs = key_pairs.into_iter();
+        let default_key_pair = key_pairs.next();
+        let mut extra_file_names = vec![];
+
+        for key_pair in key_pairs {
+            let file_name = format!("{}.json", key_pair.public_key()).replace(':', "_");
+
+            Self::_write_key_file(&extra_key_dir.join(&file_name), account_id, key_pair)?;
+            extra_file_names.push(file_name);
+        }
+        match default_key_pair {
+            Some(key_pair) => Self::_write_key_file(&path, account_id, key_pair)?,
+            None if path.exists() => fs::remove_file(path).map_err(io_error)?,
+            None => {}
+        }
+
+        for (name, is_dir) in self._list_dir(&extra_key_dir) {
+            if !is_dir && name.ends_with(".json") && !extra_file_names.contains(&name) {
+                fs::remove_file(extra_key_dir.join(name)).map_err(io_error)?;
+            }
+        }
+        if extra_key_dir.exists() && self._list_dir(&extra_key_dir).is_empty() {
+            fs::remove_dir(&extra_key_dir).map_err(io_error)?;
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), KeyStoreError> {
+        for network_id in self.get_networks()? {
+            for account_id in self.get_accounts(&network_id)? {
+                let account_id = account_id
+                    .parse()
+                    .map_err(|_| KeyStoreError::InvalidKeyData(account_id.clone()))?;
+
+                self.remove_key(&account_id, &network_id)?;
+                self.set_rotation_keys(&account_id, &network_id, vec![])?;
+            }
+        }
+        Ok(())
+    }
+
+    fn get_networks(&self) -> Result<Vec<String>, KeyStoreError> {
+        Ok(self
+            ._list_dir(&self.key_dir)
+            .into_iter()
+            .filter(|(_, is_dir)| *is_dir)
+            .map(|(name, _)| name)
+            .collect())
+    }
+
+    fn get_accounts(&self, network_id: &str) -> Result<Vec<String>, KeyStoreError> {
+        Ok(self
+            ._list_dir(&self.key_dir.join(network_id))
+            .into_iter()
+            .filter(|(_, is_dir)| !is_dir)
+            .filter_map(|(name, _)| name.strip_suffix(".json").map(String::from))
+            .collect())
+    }
+
+    fn get_rotation_keys(
+        &self,
+        account_id: &AccountId,
+        network_id: &str,
+    ) -> Result<Vec<KeyPair>, KeyStoreError> {
+        let path = self._get_rotation_file_path(account_id.as_str(), network_id);
+
+        match fs::read_to_string(&path) {
+            Ok(value) => decode_key_pairs(&path.display().to_string(), &value),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+            Err(err) => Err(io_error(err)),
+        }
+    }
+
+    fn set_rotation_keys(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+        key_pairs: Vec<KeyPair>,
+    ) -> Result<(), KeyStoreError> {
+        let path = self._get_rotation_file_path(account_id.as_str(), network_id);
+
+        if !key_pairs.is_empty() {
+            Self::_write_private_file(&path, &encode_key_pairs(&key_pairs))
+        } else if path.exists() {
+            fs::remove_file(path).map_err(io_error)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn io_error(err: std::io::Error) -> KeyStoreError {
+    KeyStoreError::Storage(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::{KeyType, SecretKey};
+
+    use super::*;
+
+    #[test]
+    fn reads_near_cli_key_files() {
+        let key_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/key_stores/near-cli");
+        let key_store = UnencryptedFileSystemKeyStore::new_with_dir(key_dir);
+        let mut accounts = key_store.get_accounts("testnet").unwrap();
+        accounts.sort();
+        assert_eq!(accounts, vec!["alice.testnet", "bob.testnet"]);
+
+        // bob.testnet was written by an older near-cli, which named the key `secret_key`.
+        for (account_id, public_key) in [
+            ("alice.testnet", "ed25519:2sNa8FSviaWvxpMKXZoqZf316sKvM32zwWP5Y7ANSn81"),
+            ("bob.testnet", "ed25519:o2udQWg3pxHupquv7hiYNTcSSwQZebnJ9BdWwLDEEr1"),
+        ] {
+            let account_id: AccountId = account_id.parse().unwrap();
+            let key_pairs = key_store.get_keys(&account_id, "testnet").unwrap();
+
+            assert_eq!(key_pairs.len(), 1);
+            assert_eq!(key_pairs[0].public_key(), public_key.parse::<PublicKey>().unwrap());
+        }
+    }
+
+    #[test]
+    fn set_keys_replaces_stale_files() {
+        let key_dir = std::env::temp_dir().join(format!("near-api-rs-test-{}", std::process::id()));
+        let mut key_store = UnencryptedFileSystemKeyStore::new_with_dir(&key_dir);
+        let account_id: AccountId = "alice.near".parse().unwrap();
+        let key_pairs = (0..3)
+            .map(|_| SecretKey::from_random(KeyType::ED25519))
+            .collect::<Vec<_>>();
+
+        key_store.set_keys(&account_id, "testnet", key_pairs.clone()).unwrap();
+        let stored = key_store.get_keys(&account_id, "testnet").unwrap();
+        assert_eq!(stored.len(), 3);
+        assert_eq!(stored[0], key_pairs[0]);
+
+        // The default key moves to the extra keys and the second key is dropped.
+        let key_pairs = vec![key_pairs[2].clone(), key_pairs[0].clone()];
+        key_store.set_keys(&account_id, "testnet", key_pairs.clone()).unwrap();
+        assert_eq!(key_store.get_keys(&account_id, "testnet").unwrap(), key_pairs);
+        assert_eq!(key_store.get_accounts("testnet").unwrap(), vec!["alice.near".to_string()]);
+
+        key_store.remove_key(&account_id, "testnet").unwrap();
+        assert!(key_store.get_keys(&account_id, "testnet").unwrap().is_empty());
+        assert!(!key_dir.join("testnet").join("alice.near").exists());
+
+        fs::remove_dir_all(key_dir).unwrap();
+    }
+}
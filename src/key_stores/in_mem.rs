@@ -1,6 +1,5 @@
-use super::{KeyPair, KeyStore};
+use super::{decode_key_pairs, encode_key_pairs, KeyPair, KeyStore, KeyStoreError};
 use core::fmt;
-use core::str::FromStr;
 use hashbrown::HashMap;
 use near_account_id::AccountId;
 
@@ -26,6 +25,30 @@ impl InMemKeyStore {
             .map(|s| s.to_string())
             .collect::<Vec<String>>()
     }
+
+    /// Storage keys of the accounts' keys as `[account_id, network_id]`, without rotation keys.
+    fn _entries(&self) -> Vec<Vec<String>> {
+        self.storage
+            .keys()
+            .map(|key| Self::_extract_storage_key(key))
+            .filter(|key| key.len() == 2)
+            .collect()
+    }
+
+    fn _set_entry(&mut self, key: String, key_pairs: Vec<KeyPair>) {
+        if key_pairs.is_empty() {
+            self.storage.remove(&key);
+        } else {
+            self.storage.insert(key, encode_key_pairs(&key_pairs));
+        }
+    }
+
+    fn _get_entry(&self, key: &str) -> Result<Vec<KeyPair>, KeyStoreError> {
+        match self.storage.get(key) {
+            Some(value) => decode_key_pairs(key, value),
+            None => Ok(vec![]),
+        }
+    }
 }
 
 impl fmt::Display for InMemKeyStore {
@@ -35,38 +58,39 @@ impl fmt::Display for InMemKeyStore {
 }
 
 impl KeyStore for InMemKeyStore {
-    fn set_key(&mut self, account_id: AccountId, network_id: &str, key_pair: KeyPair) {
-        let key = Self::_get_storage_key(account_id.as_str(), network_id);
-        let value = key_pair.to_string();
-        self.storage.insert(key, value);
+    fn get_keys(
+        &self,
+        account_id: &AccountId,
+        network_id: &str,
+    ) -> Result<Vec<KeyPair>, KeyStoreError> {
+        self._get_entry(&Self::_get_storage_key(account_id.as_str(), network_id))
     }
 
-    fn get_key(&self, account_id: AccountId, network_id: &str) -> KeyPair {
-        let key = Self::_get_storage_key(account_id.as_str(), network_id);
-        let value = self.storage.get(&key).expect("Invalid key");
-        let key_pair = KeyPair::from_str(value.as_str()).expect("Invalid key data");
-
-        key_pair
+    fn set_keys(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+        key_pairs: Vec<KeyPair>,
+    ) -> Result<(), KeyStoreError> {
+        self._set_entry(Self::_get_storage_key(account_id.as_str(), network_id), key_pairs);
+        Ok(())
     }
-    fn remove_key(&mut self, account_id: AccountId, network_id: &str) {
-        let key = Self::_get_storage_key(account_id.as_str(), network_id);
 
-        self.storage.remove(&key);
-    }
-    fn clear(&mut self) {
+    fn clear(&mut self) -> Result<(), KeyStoreError> {
         self.storage.clear();
+        Ok(())
     }
-    fn get_networks(&self) -> Vec<String> {
-        self.storage
-            .keys()
-            .map(|s| Self::_extract_storage_key(&s)[1].clone())
-            .collect()
+
+    fn get_networks(&self) -> Result<Vec<String>, KeyStoreError> {
+        Ok(self._entries().into_iter().map(|key| key[1].clone()).collect())
     }
-    fn get_accounts(&self, network_id: &str) -> Vec<String> {
-        self.storage
-            .keys()
-            .filter(|s| Self::_extract_storage_key(s)[0] == network_id)
-            .map(|s| Self::_extract_storage_key(s)[0].clone())
-            .collect()
+
+    fn get_accounts(&self, network_id: &str) -> Result<Vec<String>, KeyStoreError> {
+        Ok(self
+            ._entries()
+            .into_iter()
+            .filter(|key| key[1] == network_id)
+            .map(|key| key[0].clone())
+            .collect())
     }
 }
@@ -0,0 +1,522 @@
+use core::fmt;
+
+use async_trait::async_trait;
+use js_sys::{Array, Function, Promise, Reflect};
+use near_account_id::AccountId;
+use near_crypto::PublicKey;
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    Event, EventTarget, IdbDatabase, IdbFactory, IdbObjectStore, IdbOpenDbRequest, IdbRequest,
+    IdbTransaction, IdbTransactionMode,
+};
+
+use super::{
+    browser::{storage_error, LOCAL_STORAGE_KEY_PREFIX},
+    decode_key_pairs, encode_key_pairs, AsyncKeyStore, BrowserKeyStore, KeyPair, KeyStore,
+    KeyStoreError,
+};
+
+const INDEXED_DB_NAME: &str = "near-api-rs";
+const INDEXED_DB_VERSION: u32 = 1;
+const OBJECT_STORE_NAME: &str = "keystore";
+
+/// Keeps keys in IndexedDB under the same `prefix:account:network` keys as `BrowserKeyStore`.
+/// Works in windows and in web workers.
+#[derive(Clone)]
+pub struct IndexedDbKeyStore {
+    prefix: String,
+    db: IdbDatabase,
+}
+
+impl IndexedDbKeyStore {
+    pub async fn new() -> Result<Self, KeyStoreError> {
+        Self::new_with_prefix(LOCAL_STORAGE_KEY_PREFIX).await
+    }
+
+    pub async fn new_with_prefix(prefix: &str) -> Result<Self, KeyStoreError> {
+        let request = idb_factory()?
+            .open_with_u32(INDEXED_DB_NAME, INDEXED_DB_VERSION)
+            .map_err(storage_error)?;
+        let mut listeners = EventListeners::new(&request);
+        listeners.add("upgradeneeded", |event| {
+            let db = event
+                .target()
+                .and_then(|target| target.dyn_into::<IdbOpenDbRequest>().ok())
+                .and_then(|request| request.result().ok())
+                .and_then(|db| db.dyn_into::<IdbDatabase>().ok());
+
+            if let Some(db) = db {
+                if !db.object_store_names().contains(OBJECT_STORE_NAME) {
+                    if let Err(err) = db.create_object_store(OBJECT_STORE_NAME) {
+                        log::warn!("Fail to create key store: {:?}", err);
+                    }
+                }
+            }
+        })?;
+
+        // An older version held open in another tab blocks the upgrade until that tab closes it.
+        wait_for_event(&request, "success", &["error", "blocked"]).await?;
+        let db = request
+            .result()
+            .and_then(|db| db.dyn_into::<IdbDatabase>())
+            .map_err(storage_error)?;
+
+        Ok(Self {
+            prefix: String::from(prefix),
+            db,
+        })
+    }
+
+    /// Moves every entry of `browser_key_store` into this store, keeping keys already here, and
+    /// removes them from local storage once they are committed here. Returns the number of
+    /// accounts moved.
+    pub async fn migrate_from(
+        &mut self,
+        browser_key_store: &mut BrowserKeyStore,
+    ) -> Result<usize, KeyStoreError> {
+        let mut network_ids = browser_key_store.get_networks()?;
+        let mut migrated = 0;
+
+        network_ids.sort();
+        network_ids.dedup();
+        for network_id in network_ids {
+            for account_id in browser_key_store.get_accounts(&network_id)? {
+                let account_id: AccountId = account_id
+                    .parse()
+                    .map_err(|_| KeyStoreError::InvalidKeyData(account_id.clone()))?;
+
+                for key_pair in browser_key_store.get_keys(&account_id, &network_id)? {
+                    self.add_key_async(&account_id, &network_id, key_pair).await?;
+                }
+                let rotation_keys = browser_key_store.get_rotation_keys(&account_id, &network_id)?;
+                if !rotation_keys.is_empty() {
+                    self.set_rotation_keys_async(&account_id, &network_id, rotation_keys)
+                        .await?;
+                    browser_key_store.set_rotation_keys(&account_id, &network_id, vec![])?;
+                }
+                browser_key_store.remove_key(&account_id, &network_id)?;
+                migrated += 1;
+            }
+        }
+        Ok(migrated)
+    }
+
+    fn _get_storage_key(prefix: &str, account_id: &str, network_id: &str) -> String {
+        format!("{}:{}:{}", prefix, account_id, network_id)
+    }
+
+    fn _extract_storage_key(storage_key: &str) -> Vec<String> {
+        storage_key
+            .split(':')
+            .map(|s| s.to_string())
+            .collect::<Vec<String>>()
+    }
+
+    fn _transaction(
+        &self,
+        mode: IdbTransactionMode,
+    ) -> Result<(IdbTransaction, IdbObjectStore), KeyStoreError> {
+        let transaction = self
+            .db
+            .transaction_with_str_and_mode(OBJECT_STORE_NAME, mode)
+            .map_err(storage_error)?;
+        let object_store = transaction
+            .object_store(OBJECT_STORE_NAME)
+            .map_err(storage_error)?;
+
+        Ok((transaction, object_store))
+    }
+
+    /// Storage keys of all of this store's entries, rotation keys included.
+    async fn _entry_keys(&self) -> Result<Vec<String>, KeyStoreError> {
+        let (_, object_store) = self._transaction(IdbTransactionMode::Readonly)?;
+        let request = object_store.get_all_keys().map_err(storage_error)?;
+        let keys: Array = wait_for_request(&request).await?.unchecked_into();
+        let prefix = format!("{}:", self.prefix);
+
+        Ok(keys
+            .iter()
+            .filter_map(|key| key.as_string())
+            .filter(|key| key.starts_with(&prefix))
+            .collect())
+    }
+
+    /// Storage keys of this store's entries as `[prefix, account_id, network_id]`.
+    async fn _entries(&self) -> Result<Vec<Vec<String>>, KeyStoreError> {
+        Ok(self
+            ._entry_keys()
+            .await?
+            .iter()
+            .map(|key| Self::_extract_storage_key(key))
+            .filter(|key| key.len() == 3)
+            .collect())
+    }
+
+    async fn _get_entry(&self, key: &str) -> Result<Vec<KeyPair>, KeyStoreError> {
+        let (_, object_store) = self._transaction(IdbTransactionMode::Readonly)?;
+
+        Self::_read_entry(&object_store, key).await
+    }
+
+    async fn _read_entry(
+        object_store: &IdbObjectStore,
+        key: &str,
+    ) -> Result<Vec<KeyPair>, KeyStoreError> {
+        let request = object_store
+            .get(&JsValue::from_str(key))
+            .map_err(storage_error)?;
+
+        match wait_for_request(&request).await?.as_string() {
+            Some(value) => decode_key_pairs(key, &value),
+            None => Ok(vec![]),
+        }
+    }
+
+    fn _write_entry(
+        object_store: &IdbObjectStore,
+        key: &str,
+        key_pairs: &[KeyPair],
+    ) -> Result<IdbRequest, KeyStoreError> {
+        let key = JsValue::from_str(key);
+        let request = if key_pairs.is_empty() {
+            object_store.delete(&key)
+        } else {
+            let value = JsValue::from_str(&encode_key_pairs(key_pairs));
+
+            object_store.put_with_key(&value, &key)
+        };
+
+        request.map_err(storage_error)
+    }
+
+    async fn _set_entry(&self, key: &str, key_pairs: Vec<KeyPair>) -> Result<(), KeyStoreError> {
+        let (transaction, object_store) = self._transaction(IdbTransactionMode::Readwrite)?;
+
+        Self::_write_entry(&object_store, key, &key_pairs)?;
+        wait_for_transaction(&transaction).await
+    }
+
+    /// Reads, changes and writes an entry in one transaction, so that concurrent updates of the
+    /// same entry can not overwrite each other.
+    async fn _update_entry(
+        &self,
+        key: &str,
+        update: impl FnOnce(&mut Vec<KeyPair>),
+    ) -> Result<(), KeyStoreError> {
+        let (transaction, object_store) = self._transaction(IdbTransactionMode::Readwrite)?;
+        // The transaction stays active while the read's result is handled, as long as nothing
+        // else is awaited in between.
+        let mut key_pairs = Self::_read_entry(&object_store, key).await?;
+
+        update(&mut key_pairs);
+        Self::_write_entry(&object_store, key, &key_pairs)?;
+        wait_for_transaction(&transaction).await
+    }
+}
+
+impl fmt::Display for IndexedDbKeyStore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "IndexedDbKeyStore")
+    }
+}
+
+#[async_trait(?Send)]
+impl AsyncKeyStore for IndexedDbKeyStore {
+    async fn get_keys_async(
+        &self,
+        account_id: &AccountId,
+        network_id: &str,
+    ) -> Result<Vec<KeyPair>, KeyStoreError> {
+        let key = Self::_get_storage_key(&self.prefix, account_id.as_str(), network_id);
+
+        self._get_entry(&key).await
+    }
+
+    async fn set_keys_async(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+        key_pairs: Vec<KeyPair>,
+    ) -> Result<(), KeyStoreError> {
+        let key = Self::_get_storage_key(&self.prefix, account_id.as_str(), network_id);
+
+        self._set_entry(&key, key_pairs).await
+    }
+
+    /// Removes this store's entries, leaving entries under other prefixes.
+    async fn clear_async(&mut self) -> Result<(), KeyStoreError> {
+        let keys = self._entry_keys().await?;
+        let (transaction, object_store) = self._transaction(IdbTransactionMode::Readwrite)?;
+
+        for key in keys {
+            Self::_write_entry(&object_store, &key, &[])?;
+        }
+        wait_for_transaction(&transaction).await
+    }
+
+    async fn get_networks_async(&self) -> Result<Vec<String>, KeyStoreError> {
+        Ok(self
+            ._entries()
+            .await?
+            .into_iter()
+            .map(|key| key[2].clone())
+            .collect())
+    }
+
+    async fn get_accounts_async(&self, network_id: &str) -> Result<Vec<String>, KeyStoreError> {
+        Ok(self
+            ._entries()
+            .await?
+            .into_iter()
+            .filter(|key| key[2] == network_id)
+            .map(|key| key[1].clone())
+            .collect())
+    }
+
+    async fn add_key_async(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+        key_pair: KeyPair,
+    ) -> Result<(), KeyStoreError> {
+        let key = Self::_get_storage_key(&self.prefix, account_id.as_str(), network_id);
+
+        self._update_entry(&key, |key_pairs| {
+            if key_pairs.iter().all(|stored| stored.public_key() != key_pair.public_key()) {
+                key_pairs.push(key_pair);
+            }
+        })
+        .await
+    }
+
+    async fn remove_key_by_public_key_async(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+        public_key: &PublicKey,
+    ) -> Result<(), KeyStoreError> {
+        let key = Self::_get_storage_key(&self.prefix, account_id.as_str(), network_id);
+
+        self._update_entry(&key, |key_pairs| {
+            key_pairs.retain(|key_pair| &key_pair.public_key() != public_key);
+        })
+        .await
+    }
+}
+
+/// Reads `indexedDB` off the global object, which is a window or a worker global scope.
+fn idb_factory() -> Result<IdbFactory, KeyStoreError> {
+    let factory = Reflect::get(&js_sys::global(), &JsValue::from_str("indexedDB"))
+        .map_err(storage_error)?;
+
+    factory
+        .dyn_into::<IdbFactory>()
+        .map_err(|_| KeyStoreError::Storage("IndexedDB is not available".to_string()))
+}
+
+/// Event listeners that are removed from their target when dropped, so that none of them leak.
+struct EventListeners {
+    target: EventTarget,
+    listeners: Vec<(&'static str, Closure<dyn FnMut(Event)>)>,
+}
+
+impl EventListeners {
+    fn new(target: &EventTarget) -> Self {
+        Self {
+            target: target.clone(),
+            listeners: vec![],
+        }
+    }
+
+    fn add(
+        &mut self,
+        event: &'static str,
+        listener: impl FnMut(Event) + 'static,
+    ) -> Result<(), KeyStoreError> {
+        let listener = Closure::wrap(Box::new(listener) as Box<dyn FnMut(Event)>);
+
+        self.target
+            .add_event_listener_with_callback(event, listener.as_ref().unchecked_ref())
+            .map_err(storage_error)?;
+        self.listeners.push((event, listener));
+        Ok(())
+    }
+
+    /// Settles `callback` with the event the first time `event` fires.
+    fn settle_on(&mut self, event: &'static str, callback: Function) -> Result<(), KeyStoreError> {
+        self.add(event, move |event| {
+            let _ = callback.call1(&JsValue::NULL, &event);
+        })
+    }
+}
+
+impl Drop for EventListeners {
+    fn drop(&mut self) {
+        for (event, listener) in &self.listeners {
+            let _ = self
+                .target
+                .remove_event_listener_with_callback(event, listener.as_ref().unchecked_ref());
+        }
+    }
+}
+
+/// Waits for `resolve_on` to fire at `target`, failing if one of `reject_on` fires first.
+async fn wait_for_event(
+    target: &EventTarget,
+    resolve_on: &'static str,
+    reject_on: &[&'static str],
+) -> Result<Event, KeyStoreError> {
+    let mut listeners = EventListeners::new(target);
+    let mut added = Ok(());
+    let promise = Promise::new(&mut |resolve, reject| {
+        added = listeners.settle_on(resolve_on, resolve).and_then(|_| {
+            reject_on
+                .iter()
+                .try_for_each(|event| listeners.settle_on(*event, reject.clone()))
+        });
+    });
+    added?;
+
+    let event = JsFuture::from(promise).await.map_err(event_error)?;
+    Ok(event.unchecked_into())
+}
+
+/// Resolves with the request's result once it succeeds.
+async fn wait_for_request(request: &IdbRequest) -> Result<JsValue, KeyStoreError> {
+    wait_for_event(request, "success", &["error"]).await?;
+
+    request.result().map_err(storage_error)
+}
+
+/// Resolves once the transaction is committed.
+async fn wait_for_transaction(transaction: &IdbTransaction) -> Result<(), KeyStoreError> {
+    wait_for_event(transaction, "complete", &["error", "abort"]).await?;
+
+    Ok(())
+}
+
+/// Describes a failed request or transaction by its `error`, or by the event if it has none.
+fn event_error(event: JsValue) -> KeyStoreError {
+    let event: Event = event.unchecked_into();
+
+    if event.type_() == "blocked" {
+        return KeyStoreError::Storage(
+            "IndexedDB is blocked by another connection to an older version".to_string(),
+        );
+    }
+
+    let message = event
+        .target()
+        .and_then(|target| Reflect::get(&target, &JsValue::from_str("error")).ok())
+        .and_then(|error| Reflect::get(&error, &JsValue::from_str("message")).ok())
+        .and_then(|message| message.as_string())
+        .unwrap_or_else(|| format!("IndexedDB {} event", event.type_()));
+
+    KeyStoreError::Storage(message)
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use near_crypto::{KeyType, SecretKey};
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+
+    use super::*;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    fn account_id(account_id: &str) -> AccountId {
+        account_id.parse().unwrap()
+    }
+
+    fn random_key() -> KeyPair {
+        SecretKey::from_random(KeyType::ED25519)
+    }
+
+    /// A store with a prefix of its own, so that tests do not see each other's entries.
+    async fn empty_store(prefix: &str) -> IndexedDbKeyStore {
+        let mut key_store = IndexedDbKeyStore::new_with_prefix(prefix).await.unwrap();
+        key_store.clear_async().await.unwrap();
+        key_store
+    }
+
+    #[wasm_bindgen_test]
+    async fn round_trip() {
+        let mut key_store = empty_store("near-api-rs-test-round-trip").await;
+        let alice = account_id("alice.testnet");
+        let key_pairs = vec![random_key(), random_key()];
+
+        key_store
+            .set_keys_async(&alice, "testnet", key_pairs.clone())
+            .await
+            .unwrap();
+        assert_eq!(key_store.get_keys_async(&alice, "testnet").await.unwrap(), key_pairs);
+        assert_eq!(key_store.get_networks_async().await.unwrap(), vec!["testnet"]);
+        assert_eq!(
+            key_store.get_accounts_async("testnet").await.unwrap(),
+            vec!["alice.testnet"]
+        );
+
+        key_store
+            .remove_key_by_public_key_async(&alice, "testnet", &key_pairs[0].public_key())
+            .await
+            .unwrap();
+        assert_eq!(
+            key_store.get_keys_async(&alice, "testnet").await.unwrap(),
+            vec![key_pairs[1].clone()]
+        );
+
+        key_store.remove_key_async(&alice, "testnet").await.unwrap();
+        assert!(key_store.get_keys_async(&alice, "testnet").await.unwrap().is_empty());
+        assert!(key_store.get_networks_async().await.unwrap().is_empty());
+    }
+
+    #[wasm_bindgen_test]
+    async fn migrates_browser_key_store() {
+        let prefix = "near-api-rs-test-migration";
+        let mut browser_key_store = BrowserKeyStore::new_with_prefix(prefix);
+        let mut key_store = empty_store(prefix).await;
+        let alice = account_id("alice.testnet");
+        let bob = account_id("bob.near");
+        let existing_key = random_key();
+        let key_pairs = vec![random_key(), random_key()];
+        let rotation_keys = vec![key_pairs[0].clone(), random_key()];
+        let bob_key = random_key();
+
+        browser_key_store.clear().unwrap();
+        key_store
+            .set_key_async(&alice, "testnet", existing_key.clone())
+            .await
+            .unwrap();
+        browser_key_store
+            .set_keys(&alice, "testnet", key_pairs.clone())
+            .unwrap();
+        browser_key_store
+            .set_rotation_keys(&alice, "testnet", rotation_keys.clone())
+            .unwrap();
+        browser_key_store.set_key(&bob, "mainnet", bob_key.clone()).unwrap();
+
+        assert_eq!(key_store.migrate_from(&mut browser_key_store).await.unwrap(), 2);
+
+        // Keys already in IndexedDB stay first.
+        let mut expected = vec![existing_key];
+        expected.extend(key_pairs);
+        assert_eq!(key_store.get_keys_async(&alice, "testnet").await.unwrap(), expected);
+        assert_eq!(
+            key_store.get_rotation_keys_async(&alice, "testnet").await.unwrap(),
+            rotation_keys
+        );
+        assert_eq!(
+            key_store.get_key_async(&bob, "mainnet").await.unwrap(),
+            Some(bob_key)
+        );
+
+        assert!(browser_key_store.get_networks().unwrap().is_empty());
+        assert!(browser_key_store.get_keys(&alice, "testnet").unwrap().is_empty());
+        assert!(browser_key_store
+            .get_rotation_keys(&alice, "testnet")
+            .unwrap()
+            .is_empty());
+        assert!(browser_key_store.get_keys(&bob, "mainnet").unwrap().is_empty());
+    }
+}
@@ -1,20 +1,402 @@
 mod browser;
+mod encrypted;
+mod file_system;
 mod in_mem;
+mod indexed_db;
 
 pub use browser::*;
+pub use encrypted::*;
+pub use file_system::*;
 pub use in_mem::*;
+pub use indexed_db::*;
+use async_trait::async_trait;
 use near_account_id::AccountId;
-use near_crypto::SecretKey;
+use near_crypto::{PublicKey, SecretKey};
+use near_primitives::views::{AccessKeyInfoView, AccessKeyPermissionView};
 
 pub type KeyPair = SecretKey;
 
+/// Suffix of the storage key of an account's rotation keys.
+pub(crate) const ROTATION_KEYS_SUFFIX: &str = "rotation";
+
+/// Key-value backends keep the rotation keys of `account` on `network` under
+/// `account:network:rotation`, next to the account's keys.
+fn rotation_network_id(network_id: &str) -> String {
+    format!("{}:{}", network_id, ROTATION_KEYS_SUFFIX)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum KeyStoreError {
+    #[error("Storage error: {0}")]
+    Storage(String),
+    #[error("Invalid key data in {0}")]
+    InvalidKeyData(String),
+    #[error("Key store is locked")]
+    Locked,
+    #[error(transparent)]
+    Encryption(#[from] EncryptedKeyStoreError),
+}
+
+/// Stores any number of keys per account and network. Backends only read and write the whole
+/// list of an account's keys; the first key of the list is its default key.
 pub trait KeyStore: CloneKeyStore {
-    fn set_key(&mut self, account_id: AccountId, network_id: &str, key_pair: KeyPair);
-    fn get_key(&self, account_id: AccountId, network_id: &str) -> KeyPair;
-    fn remove_key(&mut self, account_id: AccountId, network_id: &str);
-    fn clear(&mut self);
-    fn get_networks(&self) -> Vec<String>;
-    fn get_accounts(&self, network_id: &str) -> Vec<String>;
+    fn get_keys(
+        &self,
+        account_id: &AccountId,
+        network_id: &str,
+    ) -> Result<Vec<KeyPair>, KeyStoreError>;
+    /// Replaces the account's keys. An empty list removes the account.
+    fn set_keys(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+        key_pairs: Vec<KeyPair>,
+    ) -> Result<(), KeyStoreError>;
+    fn clear(&mut self) -> Result<(), KeyStoreError>;
+    fn get_networks(&self) -> Result<Vec<String>, KeyStoreError>;
+    fn get_accounts(&self, network_id: &str) -> Result<Vec<String>, KeyStoreError>;
+
+    /// The old and new key of an unfinished key rotation of the account. By default they are the
+    /// keys of the reserved network id `<network_id>:rotation`, which stores that list networks
+    /// skip; stores can override both methods to keep them elsewhere.
+    fn get_rotation_keys(
+        &self,
+        account_id: &AccountId,
+        network_id: &str,
+    ) -> Result<Vec<KeyPair>, KeyStoreError> {
+        self.get_keys(account_id, &rotation_network_id(network_id))
+    }
+
+    /// Replaces the rotation keys. An empty list removes them.
+    fn set_rotation_keys(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+        key_pairs: Vec<KeyPair>,
+    ) -> Result<(), KeyStoreError> {
+        self.set_keys(account_id, &rotation_network_id(network_id), key_pairs)
+    }
+
+    fn get_key(
+        &self,
+        account_id: &AccountId,
+        network_id: &str,
+    ) -> Result<Option<KeyPair>, KeyStoreError> {
+        Ok(self.get_keys(account_id, network_id)?.into_iter().next())
+    }
+
+    /// Replaces all of the account's keys with `key_pair`.
+    fn set_key(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+        key_pair: KeyPair,
+    ) -> Result<(), KeyStoreError> {
+        self.set_keys(account_id, network_id, vec![key_pair])
+    }
+
+    /// Adds `key_pair` after the account's existing keys, unless it is already stored.
+    fn add_key(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+        key_pair: KeyPair,
+    ) -> Result<(), KeyStoreError> {
+        let mut key_pairs = self.get_keys(account_id, network_id)?;
+
+        if key_pairs.iter().all(|stored| stored.public_key() != key_pair.public_key()) {
+            key_pairs.push(key_pair);
+            self.set_keys(account_id, network_id, key_pairs)?;
+        }
+        Ok(())
+    }
+
+    fn get_key_by_public_key(
+        &self,
+        account_id: &AccountId,
+        network_id: &str,
+        public_key: &PublicKey,
+    ) -> Result<Option<KeyPair>, KeyStoreError> {
+        Ok(self
+            .get_keys(account_id, network_id)?
+            .into_iter()
+            .find(|key_pair| &key_pair.public_key() == public_key))
+    }
+
+    /// Removes all of the account's keys.
+    fn remove_key(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+    ) -> Result<(), KeyStoreError> {
+        self.set_keys(account_id, network_id, vec![])
+    }
+
+    fn remove_key_by_public_key(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+        public_key: &PublicKey,
+    ) -> Result<(), KeyStoreError> {
+        let mut key_pairs = self.get_keys(account_id, network_id)?;
+        let len = key_pairs.len();
+
+        key_pairs.retain(|key_pair| &key_pair.public_key() != public_key);
+        if key_pairs.len() != len {
+            self.set_keys(account_id, network_id, key_pairs)?;
+        }
+        Ok(())
+    }
+
+    /// Picks the stored key that matches one of `access_keys`, preferring full access keys.
+    fn find_matching_key(
+        &self,
+        account_id: &AccountId,
+        network_id: &str,
+        access_keys: &[AccessKeyInfoView],
+    ) -> Result<Option<KeyPair>, KeyStoreError> {
+        Ok(pick_matching_key(self.get_keys(account_id, network_id)?, access_keys))
+    }
+}
+
+/// The async counterpart of `KeyStore`, for storage that can only be reached asynchronously.
+/// Every `KeyStore` is an `AsyncKeyStore` too, so APIs taking one accept both. Its methods carry
+/// an `_async` suffix so that both traits can be imported together.
+#[async_trait(?Send)]
+pub trait AsyncKeyStore {
+    async fn get_keys_async(
+        &self,
+        account_id: &AccountId,
+        network_id: &str,
+    ) -> Result<Vec<KeyPair>, KeyStoreError>;
+    /// Replaces the account's keys. An empty list removes the account.
+    async fn set_keys_async(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+        key_pairs: Vec<KeyPair>,
+    ) -> Result<(), KeyStoreError>;
+    async fn clear_async(&mut self) -> Result<(), KeyStoreError>;
+    async fn get_networks_async(&self) -> Result<Vec<String>, KeyStoreError>;
+    async fn get_accounts_async(&self, network_id: &str) -> Result<Vec<String>, KeyStoreError>;
+
+    /// The old and new key of an unfinished key rotation of the account, stored like
+    /// `KeyStore::get_rotation_keys` stores them.
+    async fn get_rotation_keys_async(
+        &self,
+        account_id: &AccountId,
+        network_id: &str,
+    ) -> Result<Vec<KeyPair>, KeyStoreError> {
+        self.get_keys_async(account_id, &rotation_network_id(network_id))
+            .await
+    }
+
+    /// Replaces the rotation keys. An empty list removes them.
+    async fn set_rotation_keys_async(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+        key_pairs: Vec<KeyPair>,
+    ) -> Result<(), KeyStoreError> {
+        self.set_keys_async(account_id, &rotation_network_id(network_id), key_pairs)
+            .await
+    }
+
+    async fn get_key_async(
+        &self,
+        account_id: &AccountId,
+        network_id: &str,
+    ) -> Result<Option<KeyPair>, KeyStoreError> {
+        Ok(self.get_keys_async(account_id, network_id).await?.into_iter().next())
+    }
+
+    /// Replaces all of the account's keys with `key_pair`.
+    async fn set_key_async(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+        key_pair: KeyPair,
+    ) -> Result<(), KeyStoreError> {
+        self.set_keys_async(account_id, network_id, vec![key_pair]).await
+    }
+
+    /// Adds `key_pair` after the account's existing keys, unless it is already stored.
+    async fn add_key_async(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+        key_pair: KeyPair,
+    ) -> Result<(), KeyStoreError> {
+        let mut key_pairs = self.get_keys_async(account_id, network_id).await?;
+
+        if key_pairs.iter().all(|stored| stored.public_key() != key_pair.public_key()) {
+            key_pairs.push(key_pair);
+            self.set_keys_async(account_id, network_id, key_pairs).await?;
+        }
+        Ok(())
+    }
+
+    async fn get_key_by_public_key_async(
+        &self,
+        account_id: &AccountId,
+        network_id: &str,
+        public_key: &PublicKey,
+    ) -> Result<Option<KeyPair>, KeyStoreError> {
+        Ok(self
+            .get_keys(account_id, network_id)
+            .await?
+            .into_iter()
+            .find(|key_pair| &key_pair.public_key() == public_key))
+    }
+
+    /// Removes all of the account's keys.
+    async fn remove_key_async(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+    ) -> Result<(), KeyStoreError> {
+        self.set_keys_async(account_id, network_id, vec![]).await
+    }
+
+    async fn remove_key_by_public_key_async(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+        public_key: &PublicKey,
+    ) -> Result<(), KeyStoreError> {
+        let mut key_pairs = self.get_keys_async(account_id, network_id).await?;
+        let len = key_pairs.len();
+
+        key_pairs.retain(|key_pair| &key_pair.public_key() != public_key);
+        if key_pairs.len() != len {
+            self.set_keys_async(account_id, network_id, key_pairs).await?;
+        }
+        Ok(())
+    }
+
+    /// Picks the stored key that matches one of `access_keys`, preferring full access keys.
+    async fn find_matching_key_async(
+        &self,
+        account_id: &AccountId,
+        network_id: &str,
+        access_keys: &[AccessKeyInfoView],
+    ) -> Result<Option<KeyPair>, KeyStoreError> {
+        let key_pairs = self.get_keys_async(account_id, network_id).await?;
+
+        Ok(pick_matching_key(key_pairs, access_keys))
+    }
+}
+
+#[async_trait(?Send)]
+impl<T: KeyStore + ?Sized> AsyncKeyStore for T {
+    async fn get_keys_async(
+        &self,
+        account_id: &AccountId,
+        network_id: &str,
+    ) -> Result<Vec<KeyPair>, KeyStoreError> {
+        self.get_keys(account_id, network_id)
+    }
+
+    async fn set_keys_async(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+        key_pairs: Vec<KeyPair>,
+    ) -> Result<(), KeyStoreError> {
+        self.set_keys(account_id, network_id, key_pairs)
+    }
+
+    async fn clear_async(&mut self) -> Result<(), KeyStoreError> {
+        self.clear()
+    }
+
+    async fn get_networks_async(&self) -> Result<Vec<String>, KeyStoreError> {
+        self.get_networks()
+    }
+
+    async fn get_accounts_async(&self, network_id: &str) -> Result<Vec<String>, KeyStoreError> {
+        self.get_accounts(network_id)
+    }
+
+    async fn get_rotation_keys_async(
+        &self,
+        account_id: &AccountId,
+        network_id: &str,
+    ) -> Result<Vec<KeyPair>, KeyStoreError> {
+        self.get_rotation_keys(account_id, network_id)
+    }
+
+    async fn set_rotation_keys_async(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+        key_pairs: Vec<KeyPair>,
+    ) -> Result<(), KeyStoreError> {
+        self.set_rotation_keys(account_id, network_id, key_pairs)
+    }
+
+    async fn add_key_async(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+        key_pair: KeyPair,
+    ) -> Result<(), KeyStoreError> {
+        self.add_key(account_id, network_id, key_pair)
+    }
+
+    async fn remove_key_by_public_key_async(
+        &mut self,
+        account_id: &AccountId,
+        network_id: &str,
+        public_key: &PublicKey,
+    ) -> Result<(), KeyStoreError> {
+        self.remove_key_by_public_key(account_id, network_id, public_key)
+    }
+}
+
+fn pick_matching_key(
+    key_pairs: Vec<KeyPair>,
+    access_keys: &[AccessKeyInfoView],
+) -> Option<KeyPair> {
+    let matching = access_keys
+        .iter()
+        .filter_map(|access_key| {
+            let key_pair = key_pairs
+                .iter()
+                .find(|key_pair| key_pair.public_key() == access_key.public_key)?;
+            let full_access = matches!(
+                access_key.access_key.permission,
+                AccessKeyPermissionView::FullAccess
+            );
+
+            Some((full_access, key_pair))
+        })
+        .collect::<Vec<_>>();
+
+    matching
+        .iter()
+        .find(|(full_access, _)| *full_access)
+        .or_else(|| matching.first())
+        .map(|(_, key_pair)| (*key_pair).clone())
+}
+
+/// Keys are stored as a JSON array of secret key strings. Older entries hold a single bare key
+/// string and are still read.
+pub(crate) fn encode_key_pairs(key_pairs: &[KeyPair]) -> String {
+    serde_json::to_string(key_pairs).expect("Fail to serialize keys")
+}
+
+pub(crate) fn decode_key_pairs(
+    storage_key: &str,
+    value: &str,
+) -> Result<Vec<KeyPair>, KeyStoreError> {
+    let key_pairs = if value.trim_start().starts_with('[') {
+        serde_json::from_str(value).ok()
+    } else {
+        value.parse().ok().map(|key_pair| vec![key_pair])
+    };
+
+    key_pairs.ok_or_else(|| KeyStoreError::InvalidKeyData(storage_key.to_string()))
 }
 
 pub trait CloneKeyStore {
@@ -35,3 +417,103 @@ impl Clone for Box<dyn KeyStore> {
         self.clone_key_store()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::KeyType;
+    use near_primitives::views::AccessKeyView;
+
+    use super::*;
+
+    fn access_key(key_pair: &KeyPair, permission: AccessKeyPermissionView) -> AccessKeyInfoView {
+        AccessKeyInfoView {
+            public_key: key_pair.public_key(),
+            access_key: AccessKeyView {
+                nonce: 0,
+                permission,
+            },
+        }
+    }
+
+    fn function_call_permission() -> AccessKeyPermissionView {
+        AccessKeyPermissionView::FunctionCall {
+            allowance: None,
+            receiver_id: "app.near".to_string(),
+            method_names: vec![],
+        }
+    }
+
+    #[test]
+    fn decodes_legacy_and_array_entries() {
+        let key_pairs = vec![
+            SecretKey::from_random(KeyType::ED25519),
+            SecretKey::from_random(KeyType::ED25519),
+        ];
+
+        let legacy = decode_key_pairs("key", &key_pairs[0].to_string()).unwrap();
+        assert_eq!(legacy, vec![key_pairs[0].clone()]);
+
+        let encoded = encode_key_pairs(&key_pairs);
+        assert_eq!(decode_key_pairs("key", &encoded).unwrap(), key_pairs);
+
+        assert!(matches!(
+            decode_key_pairs("key", "not a key"),
+            Err(KeyStoreError::InvalidKeyData(key)) if key == "key"
+        ));
+    }
+
+    #[test]
+    fn prefers_full_access_keys() {
+        let function_call_key = SecretKey::from_random(KeyType::ED25519);
+        let full_access_key = SecretKey::from_random(KeyType::ED25519);
+        let unknown_key = SecretKey::from_random(KeyType::ED25519);
+        let key_pairs = vec![function_call_key.clone(), full_access_key.clone()];
+        let access_keys = vec![
+            access_key(&function_call_key, function_call_permission()),
+            access_key(&full_access_key, AccessKeyPermissionView::FullAccess),
+        ];
+
+        assert_eq!(
+            pick_matching_key(key_pairs.clone(), &access_keys),
+            Some(full_access_key)
+        );
+        assert_eq!(
+            pick_matching_key(key_pairs, &access_keys[..1]),
+            Some(function_call_key)
+        );
+        assert_eq!(pick_matching_key(vec![unknown_key], &access_keys), None);
+    }
+
+    #[test]
+    fn rotation_keys_are_kept_apart_from_account_keys() {
+        let account_id: AccountId = "alice.testnet".parse().unwrap();
+        let key_pair = SecretKey::from_random(KeyType::ED25519);
+        let rotation_keys = vec![
+            key_pair.clone(),
+            SecretKey::from_random(KeyType::ED25519),
+        ];
+        let mut key_store = InMemKeyStore::new();
+
+        key_store
+            .set_keys(&account_id, "testnet", vec![key_pair.clone()])
+            .unwrap();
+        key_store
+            .set_rotation_keys(&account_id, "testnet", rotation_keys.clone())
+            .unwrap();
+
+        assert_eq!(
+            key_store.get_rotation_keys(&account_id, "testnet").unwrap(),
+            rotation_keys
+        );
+        assert_eq!(key_store.get_keys(&account_id, "testnet").unwrap(), vec![key_pair]);
+        assert_eq!(key_store.get_networks().unwrap(), vec!["testnet"]);
+
+        key_store
+            .set_rotation_keys(&account_id, "testnet", vec![])
+            .unwrap();
+        assert!(key_store
+            .get_rotation_keys(&account_id, "testnet")
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -1,10 +1,12 @@
 extern crate near_jsonrpc_primitives_wasm as near_jsonrpc_primitives;
 
-mod account;
 mod client;
-mod key_stores;
 mod transaction;
 
+pub mod account;
+pub mod contract;
+pub mod key_stores;
+pub mod outcome;
 pub mod provider;
 
 pub use client::*;
@@ -0,0 +1,377 @@
+use near_account_id::AccountId;
+use near_primitives::{serialize::u128_dec_format, types::Balance};
+use serde::{ser, Deserialize, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::provider::types::{CryptoHash, FinalExecutionOutcome};
+
+/// Prefix of NEP-297 event log lines.
+pub const EVENT_LOG_PREFIX: &str = "EVENT_JSON:";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FtTransferData {
+    pub old_owner_id: AccountId,
+    pub new_owner_id: AccountId,
+    #[serde(with = "u128_dec_format")]
+    pub amount: Balance,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FtMintData {
+    pub owner_id: AccountId,
+    #[serde(with = "u128_dec_format")]
+    pub amount: Balance,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NftMintData {
+    pub owner_id: AccountId,
+    pub token_ids: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NftTransferData {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authorized_id: Option<AccountId>,
+    pub old_owner_id: AccountId,
+    pub new_owner_id: AccountId,
+    pub token_ids: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+/// The payload of a NEP-297 event, typed for the standard NEP-141 and NEP-171 events. It
+/// serializes to the NEP-297 `{"event": .., "data": ..}` shape.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    FtTransfer(Vec<FtTransferData>),
+    FtMint(Vec<FtMintData>),
+    NftMint(Vec<NftMintData>),
+    NftTransfer(Vec<NftTransferData>),
+    /// Any other event, or a standard one whose data does not match the spec.
+    Other { event: String, data: Option<Value> },
+}
+
+/// An event together with the receipt that emitted it. It serializes to the NEP-297 fields
+/// next to the receipt's, and is deserialized with its standard so the event keeps its type.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(from = "RawOutcomeEvent")]
+pub struct OutcomeEvent {
+    pub receipt_id: CryptoHash,
+    pub executor_id: AccountId,
+    pub standard: String,
+    pub version: String,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+#[derive(Deserialize)]
+struct EventLog {
+    standard: String,
+    version: String,
+    event: String,
+    data: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct RawOutcomeEvent {
+    receipt_id: CryptoHash,
+    executor_id: AccountId,
+    #[serde(flatten)]
+    log: EventLog,
+}
+
+impl From<RawOutcomeEvent> for OutcomeEvent {
+    fn from(raw: RawOutcomeEvent) -> Self {
+        Self {
+            receipt_id: raw.receipt_id,
+            executor_id: raw.executor_id,
+            event: Event::parse(&raw.log.standard, raw.log.event, raw.log.data),
+            standard: raw.log.standard,
+            version: raw.log.version,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct RawEvent {
+    event: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+}
+
+impl Serialize for Event {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let data = match self {
+            Event::FtTransfer(data) => serde_json::to_value(data).map(Some),
+            Event::FtMint(data) => serde_json::to_value(data).map(Some),
+            Event::NftMint(data) => serde_json::to_value(data).map(Some),
+            Event::NftTransfer(data) => serde_json::to_value(data).map(Some),
+            Event::Other { data, .. } => Ok(data.clone()),
+        }
+        .map_err(ser::Error::custom)?;
+
+        RawEvent {
+            event: self.name().to_string(),
+            data,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl Event {
+    pub fn name(&self) -> &str {
+        match self {
+            Event::FtTransfer(_) => "ft_transfer",
+            Event::FtMint(_) => "ft_mint",
+            Event::NftMint(_) => "nft_mint",
+            Event::NftTransfer(_) => "nft_transfer",
+            Event::Other { event, .. } => event,
+        }
+    }
+
+    fn parse(standard: &str, event: String, data: Option<Value>) -> Self {
+        let typed = match (standard, event.as_str(), &data) {
+            ("nep141", "ft_transfer", Some(data)) => {
+                serde_json::from_value(data.clone()).map(Event::FtTransfer).ok()
+            }
+            ("nep141", "ft_mint", Some(data)) => {
+                serde_json::from_value(data.clone()).map(Event::FtMint).ok()
+            }
+            ("nep171", "nft_mint", Some(data)) => {
+                serde_json::from_value(data.clone()).map(Event::NftMint).ok()
+            }
+            ("nep171", "nft_transfer", Some(data)) => {
+                serde_json::from_value(data.clone()).map(Event::NftTransfer).ok()
+            }
+            _ => None,
+        };
+
+        typed.unwrap_or(Event::Other { event, data })
+    }
+}
+
+/// Parses a single log line, returning `None` if it is not a NEP-297 event.
+pub fn parse_event_log(log_entry: &str) -> Option<(String, String, Event)> {
+    let json = log_entry.strip_prefix(EVENT_LOG_PREFIX)?;
+    let event_log: EventLog = serde_json::from_str(json.trim()).ok()?;
+    let event = Event::parse(&event_log.standard, event_log.event, event_log.data);
+
+    Some((event_log.standard, event_log.version, event))
+}
+
+/// Every NEP-297 event emitted by the receipts of `outcome`, in execution order.
+pub fn outcome_events(outcome: &FinalExecutionOutcome) -> Vec<OutcomeEvent> {
+    outcome
+        .receipts_outcome
+        .iter()
+        .flat_map(|receipt_outcome| {
+            receipt_outcome
+                .outcome
+                .logs
+                .iter()
+                .map(String::as_str)
+                .filter_map(parse_event_log)
+                .map(move |(standard, version, event)| OutcomeEvent {
+                    receipt_id: receipt_outcome.id,
+                    executor_id: receipt_outcome.outcome.executor_id.clone(),
+                    standard,
+                    version,
+                    event,
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const FT_TRANSFER_LOG: &str = concat!(
+        r#"EVENT_JSON:{"standard":"nep141","version":"1.0.0","event":"ft_transfer","#,
+        r#""data":[{"old_owner_id":"from.near","new_owner_id":"to.near","amount":"42","#,
+        r#""memo":"hi hello bonjour"},{"old_owner_id":"user1.near","new_owner_id":"user2.near","#,
+        r#""amount":"7500"}]}"#,
+    );
+    const NFT_MINT_LOG: &str = concat!(
+        r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_mint","#,
+        r#""data":[{"owner_id":"foundation.near","token_ids":["aurora","proximitylabs"]}]}"#,
+    );
+    const NFT_TRANSFER_LOG: &str = concat!(
+        r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_transfer","#,
+        r#""data":[{"old_owner_id":"user1.near","new_owner_id":"user2.near","token_ids":["meme"],"#,
+        r#""memo":"have fun!"}]}"#,
+    );
+
+    #[test]
+    fn parses_fungible_token_events() {
+        let (standard, version, event) = parse_event_log(FT_TRANSFER_LOG).unwrap();
+
+        assert_eq!(standard, "nep141");
+        assert_eq!(version, "1.0.0");
+        assert_eq!(
+            event,
+            Event::FtTransfer(vec![
+                FtTransferData {
+                    old_owner_id: "from.near".parse().unwrap(),
+                    new_owner_id: "to.near".parse().unwrap(),
+                    amount: 42,
+                    memo: Some("hi hello bonjour".to_string()),
+                },
+                FtTransferData {
+                    old_owner_id: "user1.near".parse().unwrap(),
+                    new_owner_id: "user2.near".parse().unwrap(),
+                    amount: 7500,
+                    memo: None,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_non_fungible_token_events() {
+        let (_, _, event) = parse_event_log(NFT_MINT_LOG).unwrap();
+        assert_eq!(
+            event,
+            Event::NftMint(vec![NftMintData {
+                owner_id: "foundation.near".parse().unwrap(),
+                token_ids: vec!["aurora".to_string(), "proximitylabs".to_string()],
+                memo: None,
+            }])
+        );
+
+        let (_, _, event) = parse_event_log(NFT_TRANSFER_LOG).unwrap();
+        assert_eq!(
+            event,
+            Event::NftTransfer(vec![NftTransferData {
+                authorized_id: None,
+                old_owner_id: "user1.near".parse().unwrap(),
+                new_owner_id: "user2.near".parse().unwrap(),
+                token_ids: vec!["meme".to_string()],
+                memo: Some("have fun!".to_string()),
+            }])
+        );
+    }
+
+    #[test]
+    fn ignores_logs_that_are_not_events() {
+        assert_eq!(parse_event_log("Transfer 42 from from.near to to.near"), None);
+        assert_eq!(parse_event_log(&FT_TRANSFER_LOG[1..]), None);
+        assert_eq!(parse_event_log(r#"EVENT_JSON:{"standard":"nep141","#), None);
+        assert_eq!(parse_event_log(r#"EVENT_JSON:{"standard":"nep141"}"#), None);
+    }
+
+    #[test]
+    fn keeps_unknown_and_malformed_events_untyped() {
+        let log_entry = concat!(
+            r#"EVENT_JSON: {"standard":"nep141","version":"1.0.0","event":"ft_transfer","#,
+            r#""data":[{"amount":"42"}]}"#,
+        );
+        let (_, _, event) = parse_event_log(log_entry).unwrap();
+        assert_eq!(
+            event,
+            Event::Other {
+                event: "ft_transfer".to_string(),
+                data: Some(json!([{ "amount": "42" }])),
+            }
+        );
+
+        let log_entry = concat!(
+            r#"EVENT_JSON:{"standard":"mytoken","version":"2.0.0","event":"ft_transfer","#,
+            r#""data":[{"old_owner_id":"a.near","new_owner_id":"b.near","amount":"1"}]}"#,
+        );
+        let (standard, _, event) = parse_event_log(log_entry).unwrap();
+        assert_eq!(standard, "mytoken");
+        assert!(matches!(event, Event::Other { .. }));
+
+        let log_entry = r#"EVENT_JSON:{"standard":"nep245","version":"1.0.0","event":"mt_burn"}"#;
+        let (_, _, event) = parse_event_log(log_entry).unwrap();
+        assert_eq!(
+            event,
+            Event::Other {
+                event: "mt_burn".to_string(),
+                data: None,
+            }
+        );
+    }
+
+    #[test]
+    fn collects_events_in_execution_order() {
+        let outcome: FinalExecutionOutcome =
+            serde_json::from_str(include_str!("../../fixtures/outcome/ft_transfer_call.json"))
+                .unwrap();
+        let events = outcome_events(&outcome);
+
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| (event.executor_id.as_str(), event.event.name()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("token.testnet", "ft_transfer"),
+                ("token.testnet", "ft_transfer"),
+                ("nft.testnet", "nft_transfer"),
+            ]
+        );
+        assert_eq!(events[0].receipt_id, outcome.receipts_outcome[0].id);
+        assert_eq!(events[1].receipt_id, outcome.receipts_outcome[2].id);
+        assert!(matches!(&events[1].event, Event::FtTransfer(data) if data[0].amount == 2));
+    }
+
+    #[test]
+    fn serializes_in_nep297_shape() {
+        let (standard, version, event) = parse_event_log(FT_TRANSFER_LOG).unwrap();
+        let outcome_event = OutcomeEvent {
+            receipt_id: CryptoHash::default(),
+            executor_id: "token.near".parse().unwrap(),
+            standard,
+            version,
+            event,
+        };
+        let value = serde_json::to_value(&outcome_event).unwrap();
+        let log_value: Value =
+            serde_json::from_str(FT_TRANSFER_LOG.strip_prefix(EVENT_LOG_PREFIX).unwrap()).unwrap();
+
+        assert_eq!(value["receipt_id"], json!(CryptoHash::default()));
+        assert_eq!(value["executor_id"], json!("token.near"));
+        for field in ["standard", "version", "event", "data"] {
+            assert_eq!(value[field], log_value[field]);
+        }
+        assert_eq!(
+            serde_json::from_value::<OutcomeEvent>(value).unwrap(),
+            outcome_event
+        );
+    }
+
+    #[test]
+    fn round_trips_events_of_other_standards() {
+        let outcome_event = OutcomeEvent {
+            receipt_id: CryptoHash::default(),
+            executor_id: "token.near".parse().unwrap(),
+            standard: "mytoken".to_string(),
+            version: "2.0.0".to_string(),
+            event: Event::Other {
+                event: "ft_transfer".to_string(),
+                data: Some(json!([{
+                    "old_owner_id": "a.near",
+                    "new_owner_id": "b.near",
+                    "amount": "1",
+                }])),
+            },
+        };
+        let json = serde_json::to_string(&outcome_event).unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<OutcomeEvent>(&json).unwrap(),
+            outcome_event
+        );
+    }
+}
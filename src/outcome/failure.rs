@@ -0,0 +1,176 @@
+use near_account_id::AccountId;
+use near_primitives::{
+    errors::{ActionErrorKind, InvalidTxError, TxExecutionError},
+    views::{ExecutionStatusView, FinalExecutionStatus},
+};
+use near_vm_errors::{FunctionCallErrorSer, HostError};
+use serde::{Deserialize, Serialize};
+
+use crate::provider::types::{CryptoHash, FinalExecutionOutcome};
+
+/// The first failure found while walking a `FinalExecutionOutcome`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, thiserror::Error)]
+#[error("{kind} (executed by {executor_id})")]
+pub struct ExecutionFailure {
+    /// The receipt that failed, or `None` when the transaction itself was rejected.
+    pub receipt_id: Option<CryptoHash>,
+    pub executor_id: AccountId,
+    /// Index of the failed action within its receipt, when the runtime reports one.
+    pub action_index: Option<u64>,
+    pub kind: ExecutionFailureKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, thiserror::Error)]
+pub enum ExecutionFailureKind {
+    #[error("Invalid transaction: {0}")]
+    InvalidTransaction(InvalidTxError),
+    #[error("Action failed: {0}")]
+    Action(ActionErrorKind),
+}
+
+impl ExecutionFailure {
+    fn new(
+        receipt_id: Option<CryptoHash>,
+        executor_id: AccountId,
+        error: TxExecutionError,
+    ) -> Self {
+        let (action_index, kind) = match error {
+            TxExecutionError::ActionError(error) => {
+                (error.index, ExecutionFailureKind::Action(error.kind))
+            }
+            TxExecutionError::InvalidTxError(error) => {
+                (None, ExecutionFailureKind::InvalidTransaction(error))
+            }
+        };
+
+        Self {
+            receipt_id,
+            executor_id,
+            action_index,
+            kind,
+        }
+    }
+
+    /// The message a contract panicked or aborted with, if the failure was a function call.
+    pub fn panic_message(&self) -> Option<&str> {
+        match &self.kind {
+            ExecutionFailureKind::Action(ActionErrorKind::FunctionCallError(error)) => match error {
+                FunctionCallErrorSer::ExecutionError(message) => Some(message),
+                FunctionCallErrorSer::HostError(HostError::GuestPanic { panic_msg }) => {
+                    Some(panic_msg)
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Walks the transaction outcome and then every receipt outcome, in execution order, and
+/// returns the first failure.
+pub fn check_outcome(outcome: &FinalExecutionOutcome) -> Result<(), ExecutionFailure> {
+    if let ExecutionStatusView::Failure(error) = &outcome.transaction_outcome.outcome.status {
+        let executor_id = outcome.transaction_outcome.outcome.executor_id.clone();
+        return Err(ExecutionFailure::new(None, executor_id, error.clone()));
+    }
+
+    for receipt_outcome in &outcome.receipts_outcome {
+        if let ExecutionStatusView::Failure(error) = &receipt_outcome.outcome.status {
+            let executor_id = receipt_outcome.outcome.executor_id.clone();
+            return Err(ExecutionFailure::new(
+                Some(receipt_outcome.id),
+                executor_id,
+                error.clone(),
+            ));
+        }
+    }
+
+    match &outcome.status {
+        FinalExecutionStatus::Failure(error) => Err(ExecutionFailure::new(
+            None,
+            outcome.transaction.signer_id.clone(),
+            error.clone(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::PublicKey;
+
+    use super::*;
+
+    fn fixture(json: &str) -> FinalExecutionOutcome {
+        serde_json::from_str(json).expect("Fail to parse outcome fixture")
+    }
+
+    #[test]
+    fn reports_invalid_transactions() {
+        let outcome = fixture(include_str!("../../fixtures/outcome/invalid_nonce.json"));
+        let failure = check_outcome(&outcome).unwrap_err();
+
+        assert_eq!(failure.receipt_id, None);
+        assert_eq!(failure.executor_id.as_str(), "alice.testnet");
+        assert_eq!(failure.action_index, None);
+        assert_eq!(
+            failure.kind,
+            ExecutionFailureKind::InvalidTransaction(InvalidTxError::InvalidNonce {
+                tx_nonce: 85000000000042,
+                ak_nonce: 85000000000050,
+            })
+        );
+        assert_eq!(failure.panic_message(), None);
+    }
+
+    #[test]
+    fn reports_the_failed_action_and_receipt() {
+        let outcome = fixture(include_str!("../../fixtures/outcome/add_key_already_exists.json"));
+        let failure = check_outcome(&outcome).unwrap_err();
+        let public_key: PublicKey = "ed25519:tpcJDLhZj7PKt7Wdam63iXyFhxyzZSqcwEbDKckUnd9"
+            .parse()
+            .unwrap();
+
+        assert_eq!(failure.receipt_id, Some(outcome.receipts_outcome[0].id));
+        assert_eq!(failure.executor_id.as_str(), "bob.alice.testnet");
+        assert_eq!(failure.action_index, Some(3));
+        assert_eq!(
+            failure.kind,
+            ExecutionFailureKind::Action(ActionErrorKind::AddKeyAlreadyExists {
+                account_id: "bob.alice.testnet".parse().unwrap(),
+                public_key,
+            })
+        );
+        assert_eq!(failure.panic_message(), None);
+    }
+
+    #[test]
+    fn extracts_function_call_panic_messages() {
+        let outcome = fixture(include_str!("../../fixtures/outcome/function_call_panic.json"));
+        let failure = check_outcome(&outcome).unwrap_err();
+
+        assert_eq!(failure.receipt_id, Some(outcome.receipts_outcome[1].id));
+        assert_eq!(failure.executor_id.as_str(), "counter.testnet");
+        assert_eq!(failure.action_index, Some(0));
+        assert_eq!(
+            failure.panic_message(),
+            Some("Smart contract panicked: Counter is already at zero")
+        );
+    }
+
+    #[test]
+    fn extracts_guest_panic_messages() {
+        let failure = ExecutionFailure {
+            receipt_id: None,
+            executor_id: "counter.testnet".parse().unwrap(),
+            action_index: Some(0),
+            kind: ExecutionFailureKind::Action(ActionErrorKind::FunctionCallError(
+                FunctionCallErrorSer::HostError(HostError::GuestPanic {
+                    panic_msg: "explicit guest panic".to_string(),
+                }),
+            )),
+        };
+
+        assert_eq!(failure.panic_message(), Some("explicit guest panic"));
+    }
+}
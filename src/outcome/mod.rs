@@ -0,0 +1,7 @@
+mod events;
+mod failure;
+mod receipt_tree;
+
+pub use events::*;
+pub use failure::*;
+pub use receipt_tree::*;
@@ -0,0 +1,289 @@
+use core::fmt;
+
+use hashbrown::{HashMap, HashSet};
+use near_account_id::AccountId;
+use near_primitives::{
+    serialize::u128_dec_format,
+    types::{Balance, Gas},
+    views::{ExecutionOutcomeWithIdView, ExecutionStatusView},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::provider::types::{CryptoHash, FinalExecutionOutcome};
+
+/// A receipt outcome together with the receipts it spawned.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReceiptNode {
+    pub receipt_id: CryptoHash,
+    pub executor_id: AccountId,
+    pub gas_burnt: Gas,
+    #[serde(with = "u128_dec_format")]
+    pub tokens_burnt: Balance,
+    pub logs: Vec<String>,
+    pub status: ExecutionStatusView,
+    pub children: Vec<ReceiptNode>,
+}
+
+/// The receipts of a `FinalExecutionOutcome` arranged parent to child by `receipt_ids`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReceiptTree {
+    pub transaction_hash: CryptoHash,
+    pub signer_id: AccountId,
+    pub receiver_id: AccountId,
+    pub gas_burnt: Gas,
+    #[serde(with = "u128_dec_format")]
+    pub tokens_burnt: Balance,
+    pub receipts: Vec<ReceiptNode>,
+}
+
+impl ReceiptTree {
+    pub fn new(outcome: &FinalExecutionOutcome) -> Self {
+        let outcomes = outcome
+            .receipts_outcome
+            .iter()
+            .map(|receipt_outcome| (receipt_outcome.id, receipt_outcome))
+            .collect::<HashMap<_, _>>();
+        let mut visited = HashSet::new();
+        let transaction_outcome = &outcome.transaction_outcome.outcome;
+
+        Self {
+            transaction_hash: outcome.transaction_outcome.id,
+            signer_id: outcome.transaction.signer_id.clone(),
+            receiver_id: outcome.transaction.receiver_id.clone(),
+            gas_burnt: transaction_outcome.gas_burnt,
+            tokens_burnt: transaction_outcome.tokens_burnt,
+            receipts: Self::build_nodes(&transaction_outcome.receipt_ids, &outcomes, &mut visited),
+        }
+    }
+
+    fn build_nodes(
+        receipt_ids: &[CryptoHash],
+        outcomes: &HashMap<CryptoHash, &ExecutionOutcomeWithIdView>,
+        visited: &mut HashSet<CryptoHash>,
+    ) -> Vec<ReceiptNode> {
+        let mut nodes = vec![];
+
+        for receipt_id in receipt_ids {
+            // Receipts that have not been executed yet have no outcome to show.
+            let receipt_outcome = match outcomes.get(receipt_id) {
+                Some(receipt_outcome) if visited.insert(*receipt_id) => receipt_outcome,
+                _ => continue,
+            };
+            let outcome = &receipt_outcome.outcome;
+
+            nodes.push(ReceiptNode {
+                receipt_id: *receipt_id,
+                executor_id: outcome.executor_id.clone(),
+                gas_burnt: outcome.gas_burnt,
+                tokens_burnt: outcome.tokens_burnt,
+                logs: outcome.logs.clone(),
+                status: outcome.status.clone(),
+                children: Self::build_nodes(&outcome.receipt_ids, outcomes, visited),
+            });
+        }
+
+        nodes
+    }
+
+    /// Every receipt in the tree, depth first.
+    pub fn nodes(&self) -> Vec<&ReceiptNode> {
+        fn collect<'a>(nodes: &'a [ReceiptNode], result: &mut Vec<&'a ReceiptNode>) {
+            for node in nodes {
+                result.push(node);
+                collect(&node.children, result);
+            }
+        }
+
+        let mut result = vec![];
+        collect(&self.receipts, &mut result);
+        result
+    }
+
+    /// Every log line in the tree with the account that emitted it, depth first.
+    pub fn logs(&self) -> Vec<(&AccountId, &str)> {
+        self.nodes()
+            .into_iter()
+            .flat_map(|node| {
+                node.logs
+                    .iter()
+                    .map(move |log_entry| (&node.executor_id, log_entry.as_str()))
+            })
+            .collect()
+    }
+}
+
+impl From<&FinalExecutionOutcome> for ReceiptTree {
+    fn from(outcome: &FinalExecutionOutcome) -> Self {
+        Self::new(outcome)
+    }
+}
+
+impl ReceiptNode {
+    fn fmt_with_depth(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        let indent = "  ".repeat(depth);
+
+        writeln!(
+            f,
+            "{}Receipt {} on {} (gas burnt: {}, tokens burnt: {}): {:?}",
+            indent,
+            self.receipt_id,
+            self.executor_id,
+            self.gas_burnt,
+            self.tokens_burnt,
+            self.status
+        )?;
+        for log_entry in &self.logs {
+            writeln!(f, "{}  Log: {}", indent, log_entry)?;
+        }
+        for child in &self.children {
+            child.fmt_with_depth(f, depth + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for ReceiptTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Transaction {} from {} to {} (gas burnt: {}, tokens burnt: {})",
+            self.transaction_hash,
+            self.signer_id,
+            self.receiver_id,
+            self.gas_burnt,
+            self.tokens_burnt
+        )?;
+        for node in &self.receipts {
+            node.fmt_with_depth(f, 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn fixture() -> FinalExecutionOutcome {
+        serde_json::from_str(include_str!("../../fixtures/outcome/ft_transfer_call.json"))
+            .expect("Fail to parse outcome fixture")
+    }
+
+    fn receipt_id(outcome: &FinalExecutionOutcome, executor_id: &str, nth: usize) -> CryptoHash {
+        outcome
+            .receipts_outcome
+            .iter()
+            .filter(|receipt_outcome| receipt_outcome.outcome.executor_id.as_str() == executor_id)
+            .nth(nth)
+            .map(|receipt_outcome| receipt_outcome.id)
+            .unwrap()
+    }
+
+    #[test]
+    fn links_receipts_to_their_parents() {
+        let outcome = fixture();
+        let tree = ReceiptTree::new(&outcome);
+        let transfer = receipt_id(&outcome, "token.testnet", 0);
+        let resolve = receipt_id(&outcome, "token.testnet", 1);
+        let on_transfer = receipt_id(&outcome, "market.testnet", 0);
+        let nft_transfer = receipt_id(&outcome, "nft.testnet", 0);
+
+        assert_eq!(tree.transaction_hash, outcome.transaction_outcome.id);
+        assert_eq!(tree.signer_id.as_str(), "alice.testnet");
+        assert_eq!(tree.receiver_id.as_str(), "token.testnet");
+        assert_eq!(tree.receipts.len(), 1);
+
+        let root = &tree.receipts[0];
+        assert_eq!(root.receipt_id, transfer);
+        assert_eq!(
+            root.children
+                .iter()
+                .map(|node| node.receipt_id)
+                .collect::<Vec<_>>(),
+            vec![on_transfer, resolve]
+        );
+        // The gas refund spawned by the NFT transfer has no outcome yet and is left out.
+        assert_eq!(root.children[0].children.len(), 1);
+        assert_eq!(root.children[0].children[0].receipt_id, nft_transfer);
+        assert!(root.children[0].children[0].children.is_empty());
+        assert!(root.children[1].children.is_empty());
+        assert_eq!(tree.nodes().len(), 4);
+    }
+
+    #[test]
+    fn lists_logs_depth_first() {
+        let tree = ReceiptTree::new(&fixture());
+        let logs = tree
+            .logs()
+            .into_iter()
+            .map(|(executor_id, log_entry)| (executor_id.as_str(), log_entry))
+            .collect::<Vec<_>>();
+
+        assert_eq!(logs.len(), 4);
+        assert_eq!(logs[0].0, "token.testnet");
+        assert!(logs[0].1.contains(r#""new_owner_id":"market.testnet""#));
+        assert_eq!(logs[1], ("market.testnet", "Received 10 tokens from alice.testnet"));
+        assert_eq!(logs[2].0, "nft.testnet");
+        assert!(logs[2].1.contains(r#""event":"nft_transfer""#));
+        assert_eq!(logs[3].0, "token.testnet");
+        assert!(logs[3].1.contains(r#""memo":"refund""#));
+    }
+
+    #[test]
+    fn displays_receipts_indented_by_depth() {
+        let outcome = fixture();
+        let tree = ReceiptTree::new(&outcome);
+        let output = tree.to_string();
+        let lines = output.lines().collect::<Vec<_>>();
+        let transfer = receipt_id(&outcome, "token.testnet", 0);
+        let resolve = receipt_id(&outcome, "token.testnet", 1);
+
+        assert_eq!(
+            lines[0],
+            format!(
+                "Transaction {} from alice.testnet to token.testnet \
+                 (gas burnt: 2428021223404, tokens burnt: 242802122340400000000)",
+                outcome.transaction_outcome.id
+            )
+        );
+        assert_eq!(
+            lines[1],
+            format!(
+                "  Receipt {} on token.testnet \
+                 (gas burnt: 5234566253016, tokens burnt: 523456625301600000000): \
+                 SuccessReceiptId({})",
+                transfer, resolve
+            )
+        );
+        assert!(lines[2].starts_with("    Log: EVENT_JSON:"));
+        assert!(lines[3].starts_with("    Receipt "));
+        assert_eq!(lines[4], "      Log: Received 10 tokens from alice.testnet");
+        assert!(lines[5].starts_with("      Receipt "));
+        assert!(lines[7].starts_with(&format!("    Receipt {} on token.testnet", resolve)));
+        assert_eq!(lines.len(), 9);
+    }
+
+    #[test]
+    fn serializes_to_nested_json() {
+        let tree = ReceiptTree::new(&fixture());
+        let value = serde_json::to_value(&tree).unwrap();
+
+        assert_eq!(value["signer_id"], json!("alice.testnet"));
+        assert_eq!(value["tokens_burnt"], json!("242802122340400000000"));
+        assert_eq!(
+            value["receipts"][0]["children"][0]["children"][0]["executor_id"],
+            json!("nft.testnet")
+        );
+        assert_eq!(
+            value["receipts"][0]["children"][1]["status"],
+            json!({ "SuccessValue": "Ijgi" })
+        );
+
+        let decoded: ReceiptTree = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&decoded).unwrap(), value);
+    }
+}
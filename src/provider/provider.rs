@@ -3,23 +3,117 @@ use super::{
         AccessKeyWithPublicKey, BlockChangeResult, BlockId, BlockReference, BlockResult,
         ChangeResult, ChunkId, ChunkResult, CryptoHash, EpochValidatorInfo, FinalExecutionOutcome,
         GasPrice, LightClientProof, LightClientProofRequest, NearProtocolConfig, NodeStatusResult,
-        QueryResponseKind, RpcQueryRequest, SignedTransaction,
+        QueryResponseKind, RpcQueryRequest, SignedTransaction, TxExecutionLevel,
     },
     ConnectionInfo,
 };
 use crate::{client::ClientConfig, fetch_json};
 use borsh::BorshSerialize;
+use futures::{
+    future::{select, Either},
+    pin_mut,
+};
+use futures_timer::Delay;
+use hashbrown::HashMap;
 use near_account_id::AccountId;
 use near_jsonrpc_primitives::{
-    errors::RpcError,
+    errors::{RpcError, RpcErrorKind},
     message::{Message, Response as JsonRpcResponse},
+    types::{query::RpcQueryError, transactions::RpcTransactionError},
+};
+use near_primitives::{
+    errors::{InvalidTxError, TxExecutionError},
+    serialize::to_base64,
+    types::{BlockHeight, Finality, StoreKey},
+    views::FinalExecutionStatus,
 };
-use near_primitives::{serialize::to_base64, types::StoreKey};
 use serde::Deserialize;
 use serde_json::{json, Value};
+use std::time::Duration;
 
 pub type Provider = JsonRpcProvider;
 
+const TX_POLL_INITIAL_DELAY: Duration = Duration::from_millis(500);
+const TX_POLL_MAX_DELAY: Duration = Duration::from_secs(8);
+const TX_STATUS_TIMEOUT: Duration = Duration::from_secs(60);
+const BROADCAST_MAX_ATTEMPTS: usize = 3;
+const TRANSPORT_ERROR: &str = "TRANSPORT_ERROR";
+
+fn is_handler_error(error: &RpcError, name: &str) -> bool {
+    match &error.error_struct {
+        Some(RpcErrorKind::HandlerError(cause)) => cause["name"] == name,
+        _ => false,
+    }
+}
+
+fn is_internal_error(error: &RpcError, name: &str) -> bool {
+    match &error.error_struct {
+        Some(RpcErrorKind::InternalError(cause)) => cause["name"] == name,
+        _ => false,
+    }
+}
+
+/// Errors after which the transaction may or may not have reached the network.
+fn is_ambiguous_broadcast_error(error: &RpcError) -> bool {
+    is_handler_error(error, "TIMEOUT_ERROR") || is_internal_error(error, TRANSPORT_ERROR)
+}
+
+/// The request did not get a JSON RPC response back, so the node may or may not have seen it.
+fn transport_error(message: String) -> RpcError {
+    let mut error = RpcError::new_internal_error(None, message.clone());
+    error.error_struct = Some(RpcErrorKind::InternalError(json!({
+        "name": TRANSPORT_ERROR,
+        "info": { "error_message": message },
+    })));
+    error
+}
+
+/// The error a node returns for an expired transaction, with the reason in `data` as nodes
+/// send it, since `RpcTransactionError::InvalidTransaction` does not serialize its context.
+fn expired_transaction_error() -> RpcError {
+    let mut error: RpcError = RpcTransactionError::InvalidTransaction {
+        context: InvalidTxError::Expired,
+    }
+    .into();
+    error.data = Some(json!({
+        "TxExecutionError": TxExecutionError::InvalidTxError(InvalidTxError::Expired),
+    }));
+    error
+}
+
+fn is_executed(outcome: &FinalExecutionOutcome) -> bool {
+    matches!(
+        outcome.status,
+        FinalExecutionStatus::SuccessValue(_) | FinalExecutionStatus::Failure(_)
+    )
+}
+
+/// Nodes still report some query errors inside `result` as `{"error": "...", "logs": [], ...}`.
+fn legacy_query_error(result: &Value) -> Option<RpcError> {
+    let vm_error = result.get("error")?.as_str()?.to_string();
+    let block_height = result["block_height"].as_u64().unwrap_or_default();
+    let block_hash = serde_json::from_value(result["block_hash"].clone()).unwrap_or_default();
+    let public_key = vm_error
+        .strip_prefix("access key ")
+        .and_then(|rest| rest.strip_suffix(" does not exist while viewing"))
+        .and_then(|public_key| public_key.parse().ok());
+
+    let error = match public_key {
+        Some(public_key) => RpcQueryError::UnknownAccessKey {
+            public_key,
+            block_height,
+            block_hash,
+        },
+        None => RpcQueryError::ContractExecutionError {
+            vm_error,
+            block_height,
+            block_hash,
+        },
+    };
+
+    Some(error.into())
+}
+
 #[derive(Clone)]
 pub struct JsonRpcProvider {
     connection_info: ConnectionInfo,
@@ -40,18 +134,13 @@ impl JsonRpcProvider {
         let url = &self.connection_info.url;
         let message = Message::request(method, params);
         let data: String = message.into();
-        let response = fetch_json(url, &data).await;
-
-        match response {
-            Ok(response) => {
-                let response: JsonRpcResponse = response.into_serde().unwrap();
+        let response = fetch_json(url, &data).await.map_err(|err| {
+            transport_error(format!("Failed to send JSON RPC request: {:?}", err))
+        })?;
 
-                Ok(response)
-            }
-            _ => Err(RpcError::parse_error(format!(
-                "Failed to parse JSON RPC response"
-            ))),
-        }
+        response.into_serde().map_err(|err| {
+            RpcError::parse_error(format!("Failed to parse JSON RPC response: {}", err))
+        })
     }
 
     pub async fn status(&self) -> Result<NodeStatusResult, RpcError> {
@@ -62,23 +151,90 @@ impl JsonRpcProvider {
         }
     }
 
+    /// Broadcasts with `broadcast_tx_commit`. When the broadcast times out or the transport fails,
+    /// the node is asked about the transaction hash first, and the same signed bytes are only
+    /// rebroadcast when the node has no record of it and its block hash has not expired.
     pub async fn send_transaction(
         &self,
         signed_transaction: SignedTransaction,
     ) -> Result<FinalExecutionOutcome, RpcError> {
-        let bytes = signed_transaction.try_to_vec().unwrap();
+        let bytes = signed_transaction
+            .try_to_vec()
+            .map_err(|err| RpcError::serialization_error(err.to_string()))?;
+        let tx_hash = signed_transaction.get_hash();
+        let signer_id = signed_transaction.transaction.signer_id.clone();
+        let block_hash = signed_transaction.transaction.block_hash;
+        let mut attempt = 1;
+
+        loop {
+            let broadcast_err = match self.broadcast_tx_commit(&bytes).await {
+                Ok(outcome) => return Ok(outcome),
+                Err(err) if is_ambiguous_broadcast_error(&err) => err,
+                Err(err) => return Err(err),
+            };
+
+            match self.tx_status(tx_hash, signer_id.clone()).await {
+                Ok(outcome) if is_executed(&outcome) => return Ok(outcome),
+                Ok(_) => {
+                    return self
+                        .wait_for_transaction(
+                            tx_hash,
+                            signer_id,
+                            TxExecutionLevel::ExecutedOptimistic,
+                            TX_STATUS_TIMEOUT,
+                        )
+                        .await;
+                }
+                Err(err) if is_handler_error(&err, "UNKNOWN_TRANSACTION") => {}
+                Err(err) => return Err(err),
+            }
+
+            if attempt >= BROADCAST_MAX_ATTEMPTS {
+                return Err(broadcast_err);
+            }
+            if self.is_block_hash_expired(block_hash).await? {
+                return Err(expired_transaction_error());
+            }
+
+            log::warn!(
+                "Rebroadcasting transaction {} after attempt {} failed: {}",
+                tx_hash,
+                attempt,
+                broadcast_err
+            );
+            attempt += 1;
+        }
+    }
+
+    async fn broadcast_tx_commit(&self, bytes: &[u8]) -> Result<FinalExecutionOutcome, RpcError> {
         let response = self
             .send_jsonrpc(
                 "broadcast_tx_commit".to_owned(),
-                Some(to_base64(&bytes).into()),
+                Some(to_base64(bytes).into()),
             )
-            .await;
-        match response {
-            Ok(response) => {
-                Ok(FinalExecutionOutcome::deserialize(response.result.unwrap()).unwrap())
-            }
-            Err(_) => Err(RpcError::parse_error(format!("Failed to send transaction"))),
-        }
+            .await?;
+
+        FinalExecutionOutcome::deserialize(response.result?)
+            .map_err(|err| RpcError::parse_error(format!("Failed to send transaction: {}", err)))
+    }
+
+    async fn is_block_hash_expired(&self, block_hash: CryptoHash) -> Result<bool, RpcError> {
+        let block = match self
+            .block(BlockReference::BlockId(BlockId::Hash(block_hash)))
+            .await
+        {
+            Ok(block) => block,
+            // Blocks older than the garbage collection window are long past their validity period.
+            Err(err) if is_handler_error(&err, "UNKNOWN_BLOCK") => return Ok(true),
+            Err(err) => return Err(err),
+        };
+        let latest_block = self.block(BlockReference::latest()).await?;
+        let config = self
+            .experimental_protocol_config(BlockReference::latest())
+            .await?;
+
+        Ok(latest_block.header.height.saturating_sub(block.header.height)
+            > config.transaction_validity_period)
     }
 
     pub async fn send_transaction_async(
@@ -106,12 +262,102 @@ impl JsonRpcProvider {
     ) -> Result<FinalExecutionOutcome, RpcError> {
         let tx_hash = format!("{}", tx_hash);
         let params = serde_json::to_value([tx_hash.as_str(), account_id.as_str()]).unwrap();
-        let response = self.send_jsonrpc("tx".to_owned(), Some(params)).await;
-        match response {
-            Ok(response) => {
-                Ok(FinalExecutionOutcome::deserialize(response.result.unwrap()).unwrap())
+        let response = self.send_jsonrpc("tx".to_owned(), Some(params)).await?;
+
+        FinalExecutionOutcome::deserialize(response.result?)
+            .map_err(|err| RpcError::parse_error(format!("Failed to get tx status: {}", err)))
+    }
+
+    /// Polls `tx` with backoff until the transaction reaches `level`, or fails with
+    /// `RpcTransactionError::TimeoutError` once `timeout` has elapsed.
+    pub async fn wait_for_transaction(
+        &self,
+        tx_hash: CryptoHash,
+        account_id: AccountId,
+        level: TxExecutionLevel,
+        timeout: Duration,
+    ) -> Result<FinalExecutionOutcome, RpcError> {
+        let polling = self.poll_transaction(tx_hash, account_id, level);
+        let deadline = Delay::new(timeout);
+        pin_mut!(polling);
+
+        match select(polling, deadline).await {
+            Either::Left((result, _)) => result,
+            Either::Right(_) => Err(RpcTransactionError::TimeoutError.into()),
+        }
+    }
+
+    async fn poll_transaction(
+        &self,
+        tx_hash: CryptoHash,
+        account_id: AccountId,
+        level: TxExecutionLevel,
+    ) -> Result<FinalExecutionOutcome, RpcError> {
+        let mut delay = TX_POLL_INITIAL_DELAY;
+        let mut block_heights = HashMap::new();
+
+        loop {
+            match self.tx_status(tx_hash, account_id.clone()).await {
+                Ok(outcome) => {
+                    if self
+                        .reached_level(&outcome, level, &mut block_heights)
+                        .await?
+                    {
+                        return Ok(outcome);
+                    }
+                }
+                // The node has not seen the transaction yet, or gave up waiting for it.
+                Err(err)
+                    if is_handler_error(&err, "UNKNOWN_TRANSACTION")
+                        || is_handler_error(&err, "TIMEOUT_ERROR") => {}
+                Err(err) => return Err(err),
+            }
+
+            Delay::new(delay).await;
+            delay = std::cmp::min(delay * 2, TX_POLL_MAX_DELAY);
+        }
+    }
+
+    /// `block_heights` caches the heights of outcome blocks between polls, so each poll only
+    /// fetches the final block and blocks it has not seen before.
+    async fn reached_level(
+        &self,
+        outcome: &FinalExecutionOutcome,
+        level: TxExecutionLevel,
+        block_heights: &mut HashMap<CryptoHash, BlockHeight>,
+    ) -> Result<bool, RpcError> {
+        match level {
+            TxExecutionLevel::Included => Ok(true),
+            TxExecutionLevel::ExecutedOptimistic => Ok(is_executed(outcome)),
+            TxExecutionLevel::Final if !is_executed(outcome) => Ok(false),
+            TxExecutionLevel::Final => {
+                let final_height = self
+                    .block(BlockReference::Finality(Finality::Final))
+                    .await?
+                    .header
+                    .height;
+                let block_hashes = std::iter::once(&outcome.transaction_outcome)
+                    .chain(outcome.receipts_outcome.iter())
+                    .map(|outcome| outcome.block_hash);
+
+                for block_hash in block_hashes {
+                    let height = match block_heights.get(&block_hash) {
+                        Some(height) => *height,
+                        None => {
+                            let block = self
+                                .block(BlockReference::BlockId(BlockId::Hash(block_hash)))
+                                .await?;
+                            block_heights.insert(block_hash, block.header.height);
+                            block.header.height
+                        }
+                    };
+                    if height > final_height {
+                        return Ok(false);
+                    }
+                }
+
+                Ok(true)
             }
-            Err(_) => Err(RpcError::parse_error(format!("Failed to get tx status"))),
         }
     }
 
@@ -134,31 +380,26 @@ impl JsonRpcProvider {
     }
 
     pub async fn query(&self, params: RpcQueryRequest) -> Result<QueryResponseKind, RpcError> {
-        let params = serde_json::to_value(params).unwrap();
-        let response = self.send_jsonrpc("query".to_owned(), Some(params)).await;
-        match response {
-            Ok(response) => Ok(QueryResponseKind::deserialize(response.result.unwrap()).unwrap()),
-            Err(_) => Err(RpcError::parse_error(format!("Failed to get tx status"))),
+        let params = serde_json::to_value(params)
+            .map_err(|err| RpcError::serialization_error(err.to_string()))?;
+        let response = self.send_jsonrpc("query".to_owned(), Some(params)).await?;
+        let result = response.result?;
+
+        if let Some(error) = legacy_query_error(&result) {
+            return Err(error);
         }
+
+        QueryResponseKind::deserialize(result)
+            .map_err(|err| RpcError::parse_error(format!("Failed to parse query result: {}", err)))
     }
     // async fn query<T: QueryResponseKind>(self, path: String, data: String) -> T{todo!();}
 
-    // // TODO: BlockQuery type?
     pub async fn block(&self, block_query: BlockReference) -> Result<BlockResult, RpcError> {
-        let byes = match block_query {
-            BlockReference::BlockId(block_id) => match block_id {
-                BlockId::Height(v) => v.to_string(),
-                BlockId::Hash(v) => v.to_string(),
-            },
-            _ => panic!("Missing block id"),
-        };
+        let params = json!(block_query);
+        let response = self.send_jsonrpc("block".to_owned(), Some(params)).await?;
 
-        let params = serde_json::to_value([&byes]).unwrap();
-        let response = self.send_jsonrpc("block".to_owned(), Some(params)).await;
-        match response {
-            Ok(response) => Ok(BlockResult::deserialize(response.result.unwrap()).unwrap()),
-            Err(_) => Err(RpcError::parse_error(format!("Failed to get block info"))),
-        }
+        BlockResult::deserialize(response.result?)
+            .map_err(|err| RpcError::parse_error(format!("Failed to get block info: {}", err)))
     }
     pub async fn block_changes(
         &self,
@@ -185,33 +426,31 @@ impl JsonRpcProvider {
             ))),
         }
     }
-    // // TODO: Use BlockQuery?
-    pub async fn validators(&self, block_id: BlockId) -> Result<EpochValidatorInfo, RpcError> {
+    /// Validators of the epoch containing `block_id`, or of the latest epoch for `None`.
+    pub async fn validators(
+        &self,
+        block_id: Option<BlockId>,
+    ) -> Result<EpochValidatorInfo, RpcError> {
         let params = json!([block_id]);
         let response = self
             .send_jsonrpc("validators".to_owned(), Some(params))
-            .await;
-        match response {
-            Ok(response) => Ok(EpochValidatorInfo::deserialize(response.result.unwrap()).unwrap()),
-            Err(_) => Err(RpcError::parse_error(format!(
-                "Failed to get block changes"
-            ))),
-        }
+            .await?;
+
+        EpochValidatorInfo::deserialize(response.result?)
+            .map_err(|err| RpcError::parse_error(format!("Failed to get validators: {}", err)))
     }
     pub async fn experimental_protocol_config(
         &self,
         block_reference: BlockReference,
     ) -> Result<NearProtocolConfig, RpcError> {
-        let params = json!([block_reference]);
+        let params = json!(block_reference);
         let response = self
             .send_jsonrpc("EXPERIMENTAL_protocol_config".to_owned(), Some(params))
-            .await;
-        match response {
-            Ok(response) => Ok(NearProtocolConfig::deserialize(response.result.unwrap()).unwrap()),
-            Err(_) => Err(RpcError::parse_error(format!(
-                "Failed to get block changes"
-            ))),
-        }
+            .await?;
+
+        NearProtocolConfig::deserialize(response.result?).map_err(|err| {
+            RpcError::parse_error(format!("Failed to get protocol config: {}", err))
+        })
     }
     pub async fn light_client_proof(
         &self,
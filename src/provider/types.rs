@@ -25,6 +25,8 @@ pub type LightClientProofRequest =
 
 #[derive(Serialize, Deserialize)]
 pub struct NearProtocolConfig {
+    pub epoch_length: near_primitives::types::BlockHeightDelta,
+    pub transaction_validity_period: near_primitives::types::BlockHeightDelta,
     pub runtime_config: near_primitives::runtime::config::RuntimeConfig,
 }
 
@@ -33,3 +35,14 @@ pub struct AccessKeyWithPublicKey {
     pub account_id: near_account_id::AccountId,
     pub public_key: near_crypto::PublicKey,
 }
+
+/// How far a transaction has progressed, ordered from least to most settled.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TxExecutionLevel {
+    /// The node knows about the transaction.
+    Included,
+    /// The transaction and all of its receipts have been executed.
+    ExecutedOptimistic,
+    /// Every block holding an execution outcome of the transaction is final.
+    Final,
+}